@@ -0,0 +1,44 @@
+use crate::Segment;
+use std::{
+    error::Error,
+    fmt,
+};
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct AdjacentError<K, V> {
+    segment: Segment<K>,
+    value: V,
+}
+
+impl<K, V> AdjacentError<K, V> {
+    pub fn new(segment: Segment<K>, value: V) -> AdjacentError<K, V> {
+        AdjacentError { segment, value }
+    }
+
+    pub fn segment(&self) -> &Segment<K> {
+        &self.segment
+    }
+
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> (Segment<K>, V) {
+        (self.segment, self.value)
+    }
+}
+
+impl<K, V> fmt::Display for AdjacentError<K, V>
+where
+    K: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} touches an existing segment with an equal value", self.segment)
+    }
+}
+
+impl<K, V> Error for AdjacentError<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{}
@@ -0,0 +1,31 @@
+use std::{
+    error::Error,
+    fmt,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BuildError {
+    Inverted(usize),
+    OutOfOrder(usize),
+    Overlapping(usize),
+}
+
+impl BuildError {
+    pub fn index(&self) -> usize {
+        match *self {
+            BuildError::Inverted(index) | BuildError::OutOfOrder(index) | BuildError::Overlapping(index) => index,
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Inverted(index) => write!(f, "segment at index {} is inverted", index),
+            BuildError::OutOfOrder(index) => write!(f, "segment at index {} precedes the previous segment", index),
+            BuildError::Overlapping(index) => write!(f, "segment at index {} overlaps the previous segment", index),
+        }
+    }
+}
+
+impl Error for BuildError {}
@@ -0,0 +1,16 @@
+use crate::Segment;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Change<K, V> {
+    Added(Segment<K>, V),
+    Removed(Segment<K>, V),
+    Changed(Segment<K>, V, V),
+}
+
+impl<K, V> Change<K, V> {
+    pub fn segment(&self) -> &Segment<K> {
+        match self {
+            Change::Added(segment, _) | Change::Removed(segment, _) | Change::Changed(segment, _, _) => segment,
+        }
+    }
+}
@@ -1,18 +1,53 @@
+#![cfg_attr(feature = "step", feature(step_trait))]
+
+mod macros;
 mod segment;
 mod segment_map_node;
 mod segment_map;
+mod segment_map_pooled;
+mod segment_map_arc;
 mod bounded;
 mod next;
+mod previous;
+#[cfg(feature = "step")]
+mod stepped;
+mod measure;
+mod overlap_error;
+mod adjacent_error;
+mod build_error;
+mod change;
 
 pub use crate::segment_map::{
     SegmentMap,
+    Boundaries,
+    ZipWith,
     Segments,
     Values,
     ValuesMut,
+    Cursor,
     Iter,
+    IterBelow,
+    IterCloned,
     IterMut,
     IntoIter,
 };
-pub use crate::segment::Segment;
+pub use crate::segment_map_pooled::SegmentMapPooled;
+pub use crate::segment_map_arc::{
+    SegmentMapArc,
+    SegmentMapArcIter,
+};
+pub use crate::segment::{
+    Segment,
+    Relation,
+    IntersectKind,
+};
 pub use crate::bounded::Bounded;
 pub use crate::next::Next;
+pub use crate::previous::Previous;
+#[cfg(feature = "step")]
+pub use crate::stepped::Stepped;
+pub use crate::measure::Measure;
+pub use crate::overlap_error::OverlapError;
+pub use crate::adjacent_error::AdjacentError;
+pub use crate::build_error::BuildError;
+pub use crate::change::Change;
@@ -0,0 +1,55 @@
+/// Implements `Next` and `Bounded` for a single-field tuple struct wrapping a type that
+/// already implements both, delegating to the inner value.
+///
+/// ```
+/// use segment_map::{impl_next_bounded, Segment};
+///
+/// #[derive(Clone, Debug, PartialEq, PartialOrd)]
+/// struct Id(u32);
+///
+/// impl_next_bounded!(Id, u32);
+///
+/// assert_eq!(Segment::new(Id(3), Id(6)), Segment::closed(Id(3), Id(5)));
+/// ```
+#[macro_export]
+macro_rules! impl_next_bounded {
+    ($type:ty, $inner:ty) => {
+        impl $crate::Next for $type {
+            fn next_checked(&self) -> Option<$type> { <$inner as $crate::Next>::next_checked(&self.0).map(Self) }
+        }
+
+        impl $crate::Bounded for $type {
+            fn min() -> $type { Self(<$inner as $crate::Bounded>::min()) }
+            fn max() -> $type { Self(<$inner as $crate::Bounded>::max()) }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Bounded,
+        Next,
+        Segment,
+        SegmentMap,
+    };
+
+    #[derive(Clone, Debug, PartialEq, PartialOrd)]
+    struct Id(u8);
+
+    impl_next_bounded!(Id, u8);
+
+    #[test]
+    fn test_impl_next_bounded() {
+        assert_eq!(Some(Id(4)), Id(3).next_checked());
+        assert_eq!(None, Id(255).next_checked());
+        assert_eq!(Id(0), Id::min());
+        assert_eq!(Id(255), Id::max());
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::closed(Id(3), Id(5)), 0);
+        segment_map.insert(Segment::closed(Id(6), Id(254)), 1);
+        assert_eq!(Some(&0), segment_map.get(&Id(5)));
+        assert_eq!(Some(&1), segment_map.get(&Id(6)));
+        assert_eq!(None, segment_map.get(&Id(255)));
+    }
+}
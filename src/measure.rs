@@ -0,0 +1,77 @@
+pub trait Measure {
+    type Output;
+    fn measure(lower: &Self, upper: &Self) -> Self::Output;
+    fn to_f64(measure: Self::Output) -> f64;
+}
+
+impl Measure for usize {
+    type Output = usize;
+    fn measure(lower: &usize, upper: &usize) -> usize { upper - lower }
+    fn to_f64(measure: usize) -> f64 { measure as f64 }
+}
+
+impl Measure for u8 {
+    type Output = u8;
+    fn measure(lower: &u8, upper: &u8) -> u8 { upper - lower }
+    fn to_f64(measure: u8) -> f64 { measure as f64 }
+}
+
+impl Measure for u16 {
+    type Output = u16;
+    fn measure(lower: &u16, upper: &u16) -> u16 { upper - lower }
+    fn to_f64(measure: u16) -> f64 { measure as f64 }
+}
+
+impl Measure for u32 {
+    type Output = u32;
+    fn measure(lower: &u32, upper: &u32) -> u32 { upper - lower }
+    fn to_f64(measure: u32) -> f64 { measure as f64 }
+}
+
+impl Measure for u64 {
+    type Output = u64;
+    fn measure(lower: &u64, upper: &u64) -> u64 { upper - lower }
+    fn to_f64(measure: u64) -> f64 { measure as f64 }
+}
+
+impl Measure for u128 {
+    type Output = u128;
+    fn measure(lower: &u128, upper: &u128) -> u128 { upper - lower }
+    fn to_f64(measure: u128) -> f64 { measure as f64 }
+}
+
+impl Measure for isize {
+    type Output = usize;
+    fn measure(lower: &isize, upper: &isize) -> usize { upper.wrapping_sub(*lower) as usize }
+    fn to_f64(measure: usize) -> f64 { measure as f64 }
+}
+
+impl Measure for i8 {
+    type Output = u8;
+    fn measure(lower: &i8, upper: &i8) -> u8 { upper.wrapping_sub(*lower) as u8 }
+    fn to_f64(measure: u8) -> f64 { measure as f64 }
+}
+
+impl Measure for i16 {
+    type Output = u16;
+    fn measure(lower: &i16, upper: &i16) -> u16 { upper.wrapping_sub(*lower) as u16 }
+    fn to_f64(measure: u16) -> f64 { measure as f64 }
+}
+
+impl Measure for i32 {
+    type Output = u32;
+    fn measure(lower: &i32, upper: &i32) -> u32 { upper.wrapping_sub(*lower) as u32 }
+    fn to_f64(measure: u32) -> f64 { measure as f64 }
+}
+
+impl Measure for i64 {
+    type Output = u64;
+    fn measure(lower: &i64, upper: &i64) -> u64 { upper.wrapping_sub(*lower) as u64 }
+    fn to_f64(measure: u64) -> f64 { measure as f64 }
+}
+
+impl Measure for i128 {
+    type Output = u128;
+    fn measure(lower: &i128, upper: &i128) -> u128 { upper.wrapping_sub(*lower) as u128 }
+    fn to_f64(measure: u128) -> f64 { measure as f64 }
+}
@@ -1,3 +1,9 @@
+use crate::Previous;
+use std::{
+    cmp::Reverse,
+    num::Wrapping,
+};
+
 pub trait Next: Clone + PartialOrd {
     fn next_checked(&self) -> Option<Self>;
     fn next_unchecked(&self) -> Self { self.next_checked().expect("overflow") }
@@ -50,3 +56,124 @@ impl Next for i64 {
 impl Next for i128 {
     fn next_checked(&self) -> Option<i128> { self.checked_add(1) }
 }
+
+// Floats step to the next representable value. They are only `PartialOrd`, so a map must
+// never contain NaN keys, which compare neither below nor above any segment.
+impl Next for f64 {
+    fn next_checked(&self) -> Option<f64> { Some(self.next_up()).filter(|next| next > self) }
+}
+
+impl Next for f32 {
+    fn next_checked(&self) -> Option<f32> { Some(self.next_up()).filter(|next| next > self) }
+}
+
+// Wrapping keys wrap around to the minimum after the maximum, so segments must not
+// cross the wrap boundary or the ordering the tree relies on is violated.
+impl Next for Wrapping<usize> {
+    fn next_checked(&self) -> Option<Wrapping<usize>> { Some(*self + Wrapping(1)) }
+}
+
+impl Next for Wrapping<u8> {
+    fn next_checked(&self) -> Option<Wrapping<u8>> { Some(*self + Wrapping(1)) }
+}
+
+impl Next for Wrapping<u16> {
+    fn next_checked(&self) -> Option<Wrapping<u16>> { Some(*self + Wrapping(1)) }
+}
+
+impl Next for Wrapping<u32> {
+    fn next_checked(&self) -> Option<Wrapping<u32>> { Some(*self + Wrapping(1)) }
+}
+
+impl Next for Wrapping<u64> {
+    fn next_checked(&self) -> Option<Wrapping<u64>> { Some(*self + Wrapping(1)) }
+}
+
+impl Next for Wrapping<u128> {
+    fn next_checked(&self) -> Option<Wrapping<u128>> { Some(*self + Wrapping(1)) }
+}
+
+// Reversed keys step toward the inner minimum, so `Segment<Reverse<T>>` describes a
+// descending range of the inner values.
+impl<T> Next for Reverse<T>
+where
+    T: Previous
+{
+    fn next_checked(&self) -> Option<Reverse<T>> { self.0.previous_checked().map(Reverse) }
+}
+
+#[cfg(test)]
+mod tests {
+    mod wrapping {
+        use crate::{
+            Next,
+            Segment,
+            SegmentMap,
+        };
+        use std::num::Wrapping;
+
+        #[test]
+        fn test_wrapping() {
+            assert_eq!(Some(Wrapping(4u8)), Wrapping(3u8).next_checked());
+            assert_eq!(Some(Wrapping(0u8)), Wrapping(255u8).next_checked());
+            let mut segment_map = SegmentMap::new();
+            segment_map.insert(Segment::closed(Wrapping(3u8), Wrapping(5)), 0);
+            segment_map.insert(Segment::closed(Wrapping(250u8), Wrapping(254)), 1);
+            assert_eq!(Some(&0), segment_map.get(&Wrapping(5)));
+            assert_eq!(Some(&1), segment_map.get(&Wrapping(254)));
+            assert_eq!(None, segment_map.get(&Wrapping(255)));
+        }
+    }
+
+    mod float {
+        use crate::{
+            Bounded,
+            Next,
+            Segment,
+        };
+
+        #[test]
+        fn test_float() {
+            assert_eq!(Some(f64::from_bits(1)), 0.0f64.next_checked());
+            assert_eq!(Some(1.0 + f64::EPSILON), 1.0f64.next_checked());
+            assert_eq!(Some(f64::INFINITY), f64::MAX.next_checked());
+            assert_eq!(None, f64::INFINITY.next_checked());
+            assert_eq!(None, f64::NAN.next_checked());
+            assert_eq!(Some(f32::from_bits(1)), 0.0f32.next_checked());
+            assert_eq!(f64::NEG_INFINITY, <f64 as Bounded>::min());
+            assert_eq!(f32::INFINITY, <f32 as Bounded>::max());
+            assert!(Segment::closed(0.5f64, 1.0).contains(&1.0));
+        }
+    }
+
+    mod reverse {
+        use crate::{
+            Bounded,
+            Next,
+            Segment,
+            SegmentMap,
+        };
+        use std::cmp::Reverse;
+
+        #[test]
+        fn test_reverse() {
+            assert_eq!(Some(Reverse(2u32)), Reverse(3u32).next_checked());
+            assert_eq!(None, Reverse(0u32).next_checked());
+            assert_eq!(Reverse(u32::MAX), <Reverse<u32> as Bounded>::min());
+            assert_eq!(Reverse(u32::MIN), <Reverse<u32> as Bounded>::max());
+            let segment = Segment::closed(Reverse(10u32), Reverse(5));
+            assert_eq!(Segment::new(Reverse(10), Reverse(4)), segment);
+            assert!(segment.contains(&Reverse(10)));
+            assert!(segment.contains(&Reverse(7)));
+            assert!(segment.contains(&Reverse(5)));
+            assert!(!segment.contains(&Reverse(11)));
+            assert!(!segment.contains(&Reverse(4)));
+            let mut segment_map = SegmentMap::new();
+            segment_map.insert(Segment::closed(Reverse(9u32), Reverse(5)), 0);
+            segment_map.insert(Segment::new(Reverse(4u32), Reverse(0)), 1);
+            assert_eq!(vec![&0, &1], segment_map.values().collect::<Vec<_>>());
+            assert_eq!(Some(&1), segment_map.get(&Reverse(1)));
+            assert_eq!(None, segment_map.get(&Reverse(0)));
+        }
+    }
+}
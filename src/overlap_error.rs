@@ -0,0 +1,44 @@
+use crate::Segment;
+use std::{
+    error::Error,
+    fmt,
+};
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OverlapError<K, V> {
+    segment: Segment<K>,
+    value: V,
+}
+
+impl<K, V> OverlapError<K, V> {
+    pub fn new(segment: Segment<K>, value: V) -> OverlapError<K, V> {
+        OverlapError { segment, value }
+    }
+
+    pub fn segment(&self) -> &Segment<K> {
+        &self.segment
+    }
+
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> (Segment<K>, V) {
+        (self.segment, self.value)
+    }
+}
+
+impl<K, V> fmt::Display for OverlapError<K, V>
+where
+    K: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} overlaps an existing segment", self.segment)
+    }
+}
+
+impl<K, V> Error for OverlapError<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{}
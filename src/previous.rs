@@ -0,0 +1,52 @@
+pub trait Previous: Clone + PartialOrd {
+    fn previous_checked(&self) -> Option<Self>;
+    fn previous_unchecked(&self) -> Self { self.previous_checked().expect("overflow") }
+}
+
+impl Previous for usize {
+    fn previous_checked(&self) -> Option<usize> { self.checked_sub(1) }
+}
+
+impl Previous for u8 {
+    fn previous_checked(&self) -> Option<u8> { self.checked_sub(1) }
+}
+
+impl Previous for u16 {
+    fn previous_checked(&self) -> Option<u16> { self.checked_sub(1) }
+}
+
+impl Previous for u32 {
+    fn previous_checked(&self) -> Option<u32> { self.checked_sub(1) }
+}
+
+impl Previous for u64 {
+    fn previous_checked(&self) -> Option<u64> { self.checked_sub(1) }
+}
+
+impl Previous for u128 {
+    fn previous_checked(&self) -> Option<u128> { self.checked_sub(1) }
+}
+
+impl Previous for isize {
+    fn previous_checked(&self) -> Option<isize> { self.checked_sub(1) }
+}
+
+impl Previous for i8 {
+    fn previous_checked(&self) -> Option<i8> { self.checked_sub(1) }
+}
+
+impl Previous for i16 {
+    fn previous_checked(&self) -> Option<i16> { self.checked_sub(1) }
+}
+
+impl Previous for i32 {
+    fn previous_checked(&self) -> Option<i32> { self.checked_sub(1) }
+}
+
+impl Previous for i64 {
+    fn previous_checked(&self) -> Option<i64> { self.checked_sub(1) }
+}
+
+impl Previous for i128 {
+    fn previous_checked(&self) -> Option<i128> { self.checked_sub(1) }
+}
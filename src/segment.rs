@@ -1,8 +1,46 @@
 use crate::{
     Bounded,
+    Measure,
     Next,
+    Previous,
 };
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    ops::{
+        Add,
+        BitAnd,
+        BitXor,
+        Div,
+        Range,
+        Rem,
+        Shr,
+        Sub,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Relation {
+    Disjoint,
+    Touching,
+    OverlapsLeft,
+    OverlapsRight,
+    Encloses,
+    Enclosed,
+    Equal,
+}
+
+/// How two segments intersect, as returned by `Segment::classify_intersection`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IntersectKind<K> {
+    Disjoint,
+    /// The intersection is empty, located at the given point.
+    Touch(K),
+    /// The intersection is non-empty.
+    Overlap(Segment<K>),
+}
 
+/// A half-open segment `[lower, upper)`, ordered by `lower` and then by `upper`.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Segment<K> {
     lower: K,
@@ -29,14 +67,48 @@ where
         (self.lower <= other.lower) && (other.upper <= self.upper)
     }
 
+    /// Whether the segments overlap or touch. An empty segment is connected to any segment
+    /// it lies within or at the boundary of, e.g. `[5, 5)` is connected to both `[0, 5)` and
+    /// `[5, 10)`.
     pub fn is_connected(&self, other: &Segment<K>) -> bool {
         (self.lower <= other.upper) && (other.lower <= self.upper)
     }
 
+    /// Like `is_connected`, but never connects an empty segment.
+    pub fn is_connected_nonempty(&self, other: &Segment<K>) -> bool {
+        !self.is_empty() && !other.is_empty() && self.is_connected(other)
+    }
+
+    pub fn is_adjacent(&self, other: &Segment<K>) -> bool {
+        let touching = (self.upper == other.lower) || (other.upper == self.lower);
+        let overlapping = (self.lower < other.upper) && (other.lower < self.upper);
+        touching && !overlapping
+    }
+
     pub fn is_empty(&self) -> bool {
         self.lower == self.upper
     }
 
+    pub fn relation(&self, other: &Segment<K>) -> Relation {
+        if (self.lower == other.lower) && (self.upper == other.upper) {
+            Relation::Equal
+        } else if self.encloses(other) {
+            Relation::Encloses
+        } else if other.encloses(self) {
+            Relation::Enclosed
+        } else if (self.upper == other.lower) || (other.upper == self.lower) {
+            Relation::Touching
+        } else if !self.is_connected(other) {
+            Relation::Disjoint
+        // self starts first, so it overlaps the left side of other
+        } else if self.lower < other.lower {
+            Relation::OverlapsLeft
+        // otherwise, self overlaps the right side of other
+        } else {
+            Relation::OverlapsRight
+        }
+    }
+
     pub fn lower(&self) -> &K {
         &self.lower
     }
@@ -59,6 +131,28 @@ where
         } else { None }
     }
 
+    pub fn classify_intersection(&self, other: &Segment<K>) -> IntersectKind<K> {
+        match self.intersection(other) {
+            // if intersection is empty, segments only touch
+            Some(intersection) if intersection.is_empty() => IntersectKind::Touch(intersection.lower),
+            Some(intersection) => IntersectKind::Overlap(intersection),
+            None => IntersectKind::Disjoint,
+        }
+    }
+
+    pub fn intersection_all<I>(iter: I) -> Option<Segment<K>>
+    where
+        I: IntoIterator<Item = Segment<K>>
+    {
+        let mut iter = iter.into_iter();
+        let first = iter.next()?;
+        iter.try_fold(first, |intersection, segment| intersection.intersection(&segment))
+    }
+
+    pub fn clamp_to(&self, bound: &Segment<K>) -> Option<Segment<K>> {
+        self.intersection(bound).filter(|intersection| !intersection.is_empty())
+    }
+
     pub fn span(&self, other: &Segment<K>) -> Segment<K> {
         Segment {
             lower: if self.lower < other.lower { self.lower.clone() } else { other.lower.clone() },
@@ -67,6 +161,127 @@ where
     }
 }
 
+impl<K> Segment<K>
+where
+    K: PartialOrd + Measure
+{
+    pub fn measure(&self) -> K::Output {
+        K::measure(&self.lower, &self.upper)
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: Clone + PartialOrd + Measure
+{
+    /// The measure of the intersection relative to the measure of the union, from `0.0` for
+    /// disjoint segments to `1.0` for equal segments. Empty segments yield `0.0`.
+    pub fn jaccard(&self, other: &Segment<K>) -> f64 {
+        let intersection = self.intersection(other).map_or(0.0, |intersection| K::to_f64(intersection.measure()));
+        let union = K::to_f64(self.measure()) + K::to_f64(other.measure()) - intersection;
+        // if union is empty, avoid dividing by zero
+        if union == 0.0 {
+            0.0
+        } else { intersection / union }
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: Clone + PartialOrd + Add<Output = K> + Sub<Output = K> + BitAnd<Output = K> + BitXor<Output = K> + Shr<u32, Output = K>
+{
+    /// Computed as `(lower & upper) + ((lower ^ upper) >> 1)`, which equals
+    /// `lower + (upper - lower) / 2` without overflowing, so odd widths round toward `lower`.
+    pub fn center(&self) -> K {
+        (self.lower.clone() & self.upper.clone()) + ((self.lower.clone() ^ self.upper.clone()) >> 1)
+    }
+
+    /// Collapses to an empty segment at `center` if the margin would invert the segment.
+    pub fn shrink(&self, margin: K) -> Segment<K> {
+        // compare against the width before subtracting so unsigned keys never underflow
+        let width = self.upper.clone() - self.lower.clone();
+        if (margin <= width) && (margin <= width - margin.clone()) {
+            Segment { lower: self.lower.clone() + margin.clone(), upper: self.upper.clone() - margin }
+        } else {
+            let center = self.center();
+            Segment { lower: center.clone(), upper: center }
+        }
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: Clone + Add<Output = K> + Sub<Output = K>
+{
+    pub fn grow(&self, margin: K) -> Segment<K> {
+        Segment { lower: self.lower.clone() - margin.clone(), upper: self.upper.clone() + margin }
+    }
+
+    /// Reflects the endpoints around `pivot`, swapping them so the result stays ordered.
+    /// The result is still half-open, so for discrete keys the covered points are
+    /// reflected around `pivot - 1/2`, e.g. `[1, 4)` mirrored around `5` is `[6, 9)`.
+    pub fn mirror(&self, pivot: &K) -> Segment<K>
+    where
+        K: PartialOrd
+    {
+        // reflect each endpoint by its distance from pivot, so only a result that doesn't
+        // fit can overflow
+        let reflect = |endpoint: &K| if endpoint >= pivot {
+            pivot.clone() - (endpoint.clone() - pivot.clone())
+        } else {
+            pivot.clone() + (pivot.clone() - endpoint.clone())
+        };
+        Segment { lower: reflect(&self.upper), upper: reflect(&self.lower) }
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: Clone + PartialOrd + Sub<Output = K>,
+    usize: TryFrom<K>,
+{
+    pub fn bucket_of(&self, key: &K, buckets: usize) -> Option<usize> {
+        if (buckets == 0) || !self.contains(key) {
+            return None;
+        }
+        let offset = usize::try_from(key.clone() - self.lower.clone()).ok()? as u128;
+        let width = usize::try_from(self.upper.clone() - self.lower.clone()).ok()? as u128;
+        // widen to avoid overflow, and keep keys near upper in the final bucket
+        let bucket = (offset * buckets as u128 / width) as usize;
+        Some(bucket.min(buckets - 1))
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: Clone + PartialOrd + Add<Output = K> + Sub<Output = K> + Div<Output = K> + Rem<Output = K> + TryFrom<usize>,
+    usize: TryFrom<K>,
+{
+    /// Splits into `n` contiguous pieces whose widths differ by at most one, with the
+    /// first `width % n` pieces one wider. If `n` exceeds the width, trailing pieces are empty.
+    pub fn split_into(&self, n: usize) -> Vec<Segment<K>> {
+        let width = self.upper.clone() - self.lower.clone();
+        let zero = width.clone() - width.clone();
+        let (base, extra) = match K::try_from(n) {
+            // extra is less than n, so it always fits
+            Ok(count) if n > 0 => (width.clone() / count.clone(), usize::try_from(width % count).ok().unwrap_or(0)),
+            // if n does not fit in a key, it exceeds the width, so every piece is at most one wide
+            _ => (zero, usize::try_from(width).ok().unwrap_or(0)),
+        };
+        let mut pieces = Vec::with_capacity(n);
+        let mut lower = self.lower.clone();
+        for i in 0..n {
+            let mut upper = lower.clone() + base.clone();
+            if i < extra {
+                upper = upper + K::try_from(1).ok().expect("one fits in every key");
+            }
+            pieces.push(Segment { lower, upper: upper.clone() });
+            lower = upper;
+        }
+        pieces
+    }
+}
+
 impl<K> Segment<K>
 where
     K: PartialOrd + Default
@@ -95,6 +310,36 @@ where
     pub fn open_closed(lower: K, upper: K) -> Segment<K> {
         Segment { lower: lower.next_unchecked(), upper: upper.next_unchecked() }
     }
+
+    pub fn singleton_checked(value: K) -> Option<Segment<K>> {
+        value.next_checked().map(|upper| Segment { lower: value, upper })
+    }
+
+    pub fn open_checked(lower: K, upper: K) -> Option<Segment<K>> {
+        lower.next_checked().map(|lower| Segment { lower, upper })
+    }
+
+    pub fn closed_checked(lower: K, upper: K) -> Option<Segment<K>> {
+        upper.next_checked().map(|upper| Segment { lower, upper })
+    }
+
+    pub fn open_closed_checked(lower: K, upper: K) -> Option<Segment<K>> {
+        Some(Segment { lower: lower.next_checked()?, upper: upper.next_checked()? })
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: PartialOrd + Previous
+{
+    /// The inclusive `(lower, upper)` bounds covering the same keys, or `None` if empty.
+    pub fn to_inclusive(&self) -> Option<(K, K)> {
+        // if empty, there is no inclusive equivalent
+        if self.is_empty() {
+            return None;
+        }
+        Some((self.lower.clone(), self.upper.previous_checked()?))
+    }
 }
 
 impl<K> Segment<K> 
@@ -108,6 +353,14 @@ where
     pub fn greater_than(value: K) -> Segment<K> {
         Segment { lower: value.next_unchecked(), upper: K::max() }
     }
+
+    pub fn at_most_checked(value: K) -> Option<Segment<K>> {
+        value.next_checked().map(|upper| Segment { lower: K::min(), upper })
+    }
+
+    pub fn greater_than_checked(value: K) -> Option<Segment<K>> {
+        value.next_checked().map(|lower| Segment { lower, upper: K::max() })
+    }
 }
 
 impl<K> Segment<K>
@@ -127,9 +380,280 @@ where
     }
 }
 
+impl<K> Segment<K> {
+    pub fn as_range(&self) -> Range<K>
+    where
+        K: Clone
+    {
+        self.lower.clone()..self.upper.clone()
+    }
+
+    pub fn into_range(self) -> Range<K> {
+        self.lower..self.upper
+    }
+}
+
+impl<K> Segment<K>
+where
+    K: Ord
+{
+    /// Orders by `upper` and then by `lower`, the reverse priority of the default `Ord`.
+    pub fn cmp_by_upper(&self, other: &Segment<K>) -> Ordering {
+        self.upper.cmp(&other.upper).then_with(|| self.lower.cmp(&other.lower))
+    }
+}
+
+impl<K> From<(K, K)> for Segment<K>
+where
+    K: PartialOrd
+{
+    fn from((lower, upper): (K, K)) -> Segment<K> {
+        debug_assert!(lower <= upper, "lower must not exceed upper");
+        Segment { lower, upper }
+    }
+}
+
+impl<K> From<Segment<K>> for (K, K) {
+    fn from(segment: Segment<K>) -> (K, K) {
+        (segment.lower, segment.upper)
+    }
+}
+
+impl<K> Add<K> for Segment<K>
+where
+    K: Clone + Add<Output = K>
+{
+    type Output = Segment<K>;
+
+    fn add(self, offset: K) -> Segment<K> {
+        Segment { lower: self.lower + offset.clone(), upper: self.upper + offset }
+    }
+}
+
+impl<K> Sub<K> for Segment<K>
+where
+    K: Clone + Sub<Output = K>
+{
+    type Output = Segment<K>;
+
+    fn sub(self, offset: K) -> Segment<K> {
+        Segment { lower: self.lower - offset.clone(), upper: self.upper - offset }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K> arbitrary::Arbitrary<'a> for Segment<K>
+where
+    K: arbitrary::Arbitrary<'a> + PartialOrd
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Segment<K>> {
+        let lower = K::arbitrary(u)?;
+        let upper = K::arbitrary(u)?;
+        // order the endpoints so the segment is never inverted
+        if lower > upper {
+            Ok(Segment { lower: upper, upper: lower })
+        } else {
+            Ok(Segment { lower, upper })
+        }
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl<K> borsh::BorshSerialize for Segment<K>
+where
+    K: borsh::BorshSerialize
+{
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        self.lower.serialize(writer)?;
+        self.upper.serialize(writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl<K> borsh::BorshDeserialize for Segment<K>
+where
+    K: borsh::BorshDeserialize + PartialOrd
+{
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Segment<K>> {
+        let lower = K::deserialize_reader(reader)?;
+        let upper = K::deserialize_reader(reader)?;
+        // inverted segments are never valid
+        if lower > upper {
+            Err(borsh::io::Error::new(borsh::io::ErrorKind::InvalidData, "segment must not be inverted"))
+        } else {
+            Ok(Segment { lower, upper })
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Segment;
+    use crate::{
+        IntersectKind,
+        Relation,
+        Segment,
+    };
+
+    #[test]
+    fn test_checked() {
+        assert_eq!(Some(Segment::new(254u8, 255)), Segment::singleton_checked(254u8));
+        assert_eq!(None, Segment::singleton_checked(255u8));
+
+        assert_eq!(Some(Segment::new(1u8, 255)), Segment::open_checked(0u8, 255));
+        assert_eq!(None, Segment::open_checked(255u8, 255));
+
+        assert_eq!(Some(Segment::new(0u8, 255)), Segment::closed_checked(0u8, 254));
+        assert_eq!(None, Segment::closed_checked(0u8, 255));
+
+        assert_eq!(Some(Segment::new(1u8, 255)), Segment::open_closed_checked(0u8, 254));
+        assert_eq!(None, Segment::open_closed_checked(0u8, 255));
+        assert_eq!(None, Segment::open_closed_checked(255u8, 255));
+
+        assert_eq!(Some(Segment::new(0u8, 255)), Segment::at_most_checked(254u8));
+        assert_eq!(None, Segment::at_most_checked(255u8));
+
+        assert_eq!(Some(Segment::new(255u8, 255)), Segment::greater_than_checked(254u8));
+        assert_eq!(None, Segment::greater_than_checked(255u8));
+    }
+
+    #[test]
+    fn test_center() {
+        assert_eq!(5, Segment::new(0, 10).center());
+        assert_eq!(4, Segment::new(0, 9).center());
+        assert_eq!(250u8, Segment::new(245u8, 255).center());
+        assert_eq!(-2, Segment::new(-3, 0).center());
+        assert_eq!(-1i8, Segment::new(-128i8, 127).center());
+        assert_eq!(127i8, Segment::new(127i8, 127).center());
+        assert_eq!(-1, Segment::new(i32::MIN, i32::MAX).center());
+        assert_eq!(u64::MAX - 1, Segment::new(u64::MAX - 2, u64::MAX).center());
+    }
+
+    #[test]
+    fn test_from_tuple() {
+        let segment: Segment<i32> = (3, 7).into();
+        assert_eq!(Segment::new(3, 7), segment);
+        assert_eq!((3, 7), segment.into());
+    }
+
+    #[test]
+    fn test_grow_shrink() {
+        assert_eq!(Segment::new(3, 13), Segment::new(5, 11).grow(2));
+        assert_eq!(Segment::new(7, 9), Segment::new(5, 11).shrink(2));
+        assert_eq!(Segment::new(8, 8), Segment::new(5, 11).shrink(3));
+        assert_eq!(Segment::new(8, 8), Segment::new(5, 11).shrink(4));
+        assert_eq!(Segment::new(3u32, 3), Segment::new(2u32, 4).shrink(5));
+        assert_eq!(Segment::new(3u32, 3), Segment::new(2u32, 4).shrink(u32::MAX));
+        assert_eq!(Segment::new(252u8, 252), Segment::new(250u8, 255).shrink(200));
+        assert_eq!(Segment::new(251u8, 254), Segment::new(250u8, 255).shrink(1));
+    }
+
+    #[test]
+    fn test_is_connected_empty() {
+        let cases = [
+            // empty at boundary
+            (Segment::new(5, 5), Segment::new(0, 5), true, false),
+            (Segment::new(5, 5), Segment::new(5, 10), true, false),
+            // empty inside
+            (Segment::new(3, 3), Segment::new(0, 5), true, false),
+            // empty outside
+            (Segment::new(7, 7), Segment::new(0, 5), false, false),
+            // both empty
+            (Segment::new(5, 5), Segment::new(5, 5), true, false),
+            (Segment::new(4, 4), Segment::new(5, 5), false, false),
+            // nonempty touching, overlapping, and disjoint
+            (Segment::new(0, 5), Segment::new(5, 10), true, true),
+            (Segment::new(0, 6), Segment::new(5, 10), true, true),
+            (Segment::new(0, 4), Segment::new(5, 10), false, false),
+        ];
+        for (a, b, connected, connected_nonempty) in cases.iter() {
+            assert_eq!(*connected, a.is_connected(b), "{:?} {:?}", a, b);
+            assert_eq!(*connected, b.is_connected(a), "{:?} {:?}", b, a);
+            assert_eq!(*connected_nonempty, a.is_connected_nonempty(b), "{:?} {:?}", a, b);
+            assert_eq!(*connected_nonempty, b.is_connected_nonempty(a), "{:?} {:?}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_is_adjacent() {
+        assert!(Segment::new(0, 5).is_adjacent(&Segment::new(5, 10)));
+        assert!(Segment::new(5, 10).is_adjacent(&Segment::new(0, 5)));
+        assert!(!Segment::new(0, 6).is_adjacent(&Segment::new(5, 10)));
+        assert!(!Segment::new(0, 4).is_adjacent(&Segment::new(5, 10)));
+        assert!(!Segment::new(0, 10).is_adjacent(&Segment::new(0, 10)));
+        assert!(!Segment::new(0, 10).is_adjacent(&Segment::new(2, 10)));
+    }
+
+    #[test]
+    fn test_bucket_of() {
+        assert_eq!(Some(3), Segment::new(0, 10).bucket_of(&7, 5));
+        assert_eq!(Some(0), Segment::new(0, 10).bucket_of(&0, 5));
+        assert_eq!(Some(4), Segment::new(0, 10).bucket_of(&9, 5));
+        assert_eq!(Some(2), Segment::new(0, 10).bucket_of(&9, 3));
+        assert_eq!(Some(1), Segment::new(10, 13).bucket_of(&11, 5));
+        assert_eq!(None, Segment::new(0, 10).bucket_of(&10, 5));
+        assert_eq!(None, Segment::new(0, 10).bucket_of(&5, 0));
+        assert_eq!(Some(u8::MAX as usize - 1), Segment::new(0u8, u8::MAX).bucket_of(&254, u8::MAX as usize));
+    }
+
+    #[test]
+    fn test_mirror() {
+        assert_eq!(Segment::new(6, 9), Segment::new(1, 4).mirror(&5));
+        assert_eq!(Segment::new(1, 4), Segment::new(6, 9).mirror(&5));
+        assert_eq!(Segment::new(-4, -1), Segment::new(1, 4).mirror(&0));
+        assert_eq!(Segment::new(196u8, 199), Segment::new(201u8, 204).mirror(&200));
+        assert_eq!(Segment::new(250u8, 255), Segment::new(145u8, 150).mirror(&200));
+        assert_eq!(Segment::new(i32::MAX - 2, i32::MAX), Segment::new(i32::MAX - 4, i32::MAX - 2).mirror(&(i32::MAX - 2)));
+    }
+
+    #[test]
+    fn test_to_inclusive() {
+        assert_eq!(Some((3, 6)), Segment::new(3, 7).to_inclusive());
+        assert_eq!(Some((3, 3)), Segment::new(3, 4).to_inclusive());
+        assert_eq!(None, Segment::new(3, 3).to_inclusive());
+        assert_eq!(Some((0u8, 254)), Segment::new(0u8, 255).to_inclusive());
+    }
+
+    #[test]
+    fn test_jaccard() {
+        assert_eq!(1.0 / 3.0, Segment::new(0, 10).jaccard(&Segment::new(5, 15)));
+        assert_eq!(0.5, Segment::new(0, 10).jaccard(&Segment::new(0, 5)));
+        assert_eq!(1.0, Segment::new(0, 10).jaccard(&Segment::new(0, 10)));
+        assert_eq!(0.0, Segment::new(0, 10).jaccard(&Segment::new(10, 20)));
+        assert_eq!(0.0, Segment::new(0, 10).jaccard(&Segment::new(15, 20)));
+        assert_eq!(0.0, Segment::new(5, 5).jaccard(&Segment::new(5, 5)));
+    }
+
+    #[test]
+    fn test_as_range() {
+        let values = (0..5).collect::<Vec<_>>();
+        assert_eq!(&[1, 2], &values[Segment::new(1, 3).as_range()]);
+        assert_eq!(1..3, Segment::new(1, 3).into_range());
+    }
+
+    #[test]
+    fn test_cmp_by_upper() {
+        let mut segments = vec![Segment::new(2, 8), Segment::new(5, 6), Segment::new(0, 8)];
+        segments.sort();
+        assert_eq!(vec![Segment::new(0, 8), Segment::new(2, 8), Segment::new(5, 6)], segments);
+        segments.sort_by(Segment::cmp_by_upper);
+        assert_eq!(vec![Segment::new(5, 6), Segment::new(0, 8), Segment::new(2, 8)], segments);
+    }
+
+    #[test]
+    fn test_split_into() {
+        assert_eq!(vec![Segment::new(0, 4), Segment::new(4, 7), Segment::new(7, 10)], Segment::new(0, 10).split_into(3));
+        assert_eq!(vec![Segment::new(0, 1), Segment::new(1, 2), Segment::new(2, 2)], Segment::new(0, 2).split_into(3));
+        assert!(Segment::new(0, 10).split_into(0).is_empty());
+        assert_eq!(vec![Segment::new(0u64, u64::MAX / 2 + 1), Segment::new(u64::MAX / 2 + 1, u64::MAX)], Segment::new(0u64, u64::MAX).split_into(2));
+        assert_eq!(vec![Segment::new(254u8, 255), Segment::new(255, 255), Segment::new(255, 255)], Segment::new(254u8, 255).split_into(3));
+        assert_eq!(300, Segment::new(0u8, 2).split_into(300).len());
+    }
+
+    #[test]
+    fn test_add_sub() {
+        assert_eq!(Segment::new(5, 9), Segment::new(3, 7) + 2);
+        assert_eq!(Segment::new(1, 5), Segment::new(3, 7) - 2);
+    }
 
     #[test]
     fn test_contains() {
@@ -227,6 +751,60 @@ mod tests {
         assert!(!Segment::new(0, 6).encloses(&Segment::new(10, 16)));
     }
 
+    #[test]
+    fn test_relation() {
+        // -----[-----)-----
+        //                   -> Equal
+        // -----[-----)-----
+        assert_eq!(Relation::Equal, Segment::new(5, 11).relation(&Segment::new(5, 11)));
+
+        // -----[-----)-----
+        //                   -> Encloses
+        // -------[-)-------
+        assert_eq!(Relation::Encloses, Segment::new(5, 11).relation(&Segment::new(7, 9)));
+
+        // -------[-)-------
+        //                   -> Enclosed
+        // -----[-----)-----
+        assert_eq!(Relation::Enclosed, Segment::new(7, 9).relation(&Segment::new(5, 11)));
+
+        // -----[-----)-----
+        //                   -> OverlapsLeft
+        // -------[-----)---
+        assert_eq!(Relation::OverlapsLeft, Segment::new(5, 11).relation(&Segment::new(7, 13)));
+
+        // -------[-----)---
+        //                   -> OverlapsRight
+        // -----[-----)-----
+        assert_eq!(Relation::OverlapsRight, Segment::new(7, 13).relation(&Segment::new(5, 11)));
+
+        // --[-----)--------
+        //                   -> Touching
+        // --------[-----)--
+        assert_eq!(Relation::Touching, Segment::new(2, 8).relation(&Segment::new(8, 14)));
+
+        // [-----)----------
+        //                   -> Disjoint
+        // ----------[-----)
+        assert_eq!(Relation::Disjoint, Segment::new(0, 6).relation(&Segment::new(10, 16)));
+    }
+
+    #[test]
+    fn test_classify_intersection() {
+        assert_eq!(IntersectKind::Disjoint, Segment::new(0, 5).classify_intersection(&Segment::new(6, 10)));
+        assert_eq!(IntersectKind::Touch(5), Segment::new(0, 5).classify_intersection(&Segment::new(5, 10)));
+        assert_eq!(IntersectKind::Touch(5), Segment::new(0, 10).classify_intersection(&Segment::new(5, 5)));
+        assert_eq!(IntersectKind::Overlap(Segment::new(4, 5)), Segment::new(0, 5).classify_intersection(&Segment::new(4, 10)));
+    }
+
+    #[test]
+    fn test_intersection_all() {
+        assert_eq!(Some(Segment::new(4, 6)), Segment::intersection_all(vec![Segment::new(0, 6), Segment::new(2, 8), Segment::new(4, 10)]));
+        assert_eq!(Some(Segment::new(6, 6)), Segment::intersection_all(vec![Segment::new(0, 6), Segment::new(6, 8), Segment::new(4, 10)]));
+        assert_eq!(None, Segment::intersection_all(vec![Segment::new(0, 6), Segment::new(8, 10), Segment::new(4, 10)]));
+        assert_eq!(None, Segment::<i32>::intersection_all(vec![]));
+    }
+
     #[test]
     fn test_intersection() {
         // -----[-----)-----
@@ -295,6 +873,29 @@ mod tests {
         assert_eq!(None, Segment::new(0, 6).intersection(&Segment::new(10, 16)));
     }
 
+    #[test]
+    fn test_clamp_to() {
+        // ---[-----------)-
+        //                   -> -----[-----)-----
+        // -----[-----)-----
+        assert_eq!(Some(Segment::new(5, 11)), Segment::new(3, 15).clamp_to(&Segment::new(5, 11)));
+
+        // -------[---)-----
+        //                   -> -------[---)-----
+        // -----[-----)-----
+        assert_eq!(Some(Segment::new(7, 11)), Segment::new(7, 11).clamp_to(&Segment::new(5, 11)));
+
+        // --[-----)--------
+        //                   -> None
+        // --------[-----)--
+        assert_eq!(None, Segment::new(2, 8).clamp_to(&Segment::new(8, 14)));
+
+        // [-----)----------
+        //                   -> None
+        // ----------[-----)
+        assert_eq!(None, Segment::new(0, 6).clamp_to(&Segment::new(10, 16)));
+    }
+
     #[test]
     fn test_is_connected() {
         // -----[-----)-----
@@ -1,9 +1,40 @@
 use crate::{
     segment_map_node::SegmentMapNode,
+    AdjacentError,
+    Bounded,
+    BuildError,
+    Change,
+    Measure,
+    Next,
+    OverlapError,
     Segment,
 };
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    convert::TryFrom,
+    fmt,
+    hash::{
+        Hash,
+        Hasher,
+    },
+    iter::{
+        Peekable,
+        Sum,
+    },
+    ops::{
+        Add,
+        Range,
+        Rem,
+        Sub,
+    },
+};
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone)]
 pub struct SegmentMap<K, V> {
     root: Option<SegmentMapNode<K, V>>,
 }
@@ -16,245 +47,4030 @@ where
         SegmentMap { root: None }
     }
 
+    fn from_sorted(entries: Vec<(Segment<K>, V)>) -> SegmentMap<K, V> {
+        let len = entries.len();
+        SegmentMap { root: SegmentMapNode::from_sorted(&mut entries.into_iter(), len) }
+    }
+
+    pub fn from_sorted_checked(entries: Vec<(Segment<K>, V)>) -> Result<SegmentMap<K, V>, BuildError> {
+        SegmentMap::check_sorted(&entries)?;
+        Ok(SegmentMap::from_sorted(entries))
+    }
+
+    fn check_sorted(entries: &[(Segment<K>, V)]) -> Result<(), BuildError> {
+        let mut previous: Option<&Segment<K>> = None;
+        for (index, (segment, _)) in entries.iter().enumerate() {
+            if segment.lower() > segment.upper() {
+                return Err(BuildError::Inverted(index));
+            }
+            if let Some(previous) = previous {
+                // segment must start at or after previous ends, and duplicates are not allowed
+                if (segment.lower() < previous.upper()) || (segment == previous) {
+                    // if segment sorts before previous, input is out of order, otherwise they overlap
+                    let out_of_order = (segment.lower() < previous.lower()) ||
+                        ((segment.lower() == previous.lower()) && (segment.upper() < previous.upper()));
+                    return Err(if out_of_order { BuildError::OutOfOrder(index) } else { BuildError::Overlapping(index) });
+                }
+            }
+            previous = Some(segment);
+        }
+        Ok(())
+    }
+
+    pub fn from_sorted_merge<I, J>(sources: I) -> SegmentMap<K, V>
+    where
+        I: IntoIterator<Item = J>,
+        J: IntoIterator<Item = (Segment<K>, V)>,
+    {
+        let mut sources = sources.into_iter().map(|source| source.into_iter().peekable()).collect::<Vec<_>>();
+        let mut entries: Vec<(Segment<K>, V)> = Vec::new();
+        loop {
+            // find the source whose next segment sorts first
+            let mut first: Option<(usize, &Segment<K>)> = None;
+            for (index, source) in sources.iter_mut().enumerate() {
+                if let Some((segment, _)) = source.peek() {
+                    let precedes = first.is_none_or(|(_, first)| {
+                        (segment.lower() < first.lower()) || ((segment.lower() == first.lower()) && (segment.upper() < first.upper()))
+                    });
+                    if precedes {
+                        first = Some((index, segment));
+                    }
+                }
+            }
+            let index = match first {
+                Some((index, _)) => index,
+                None => break,
+            };
+            let (segment, value) = sources[index].next().expect("source has entry");
+            // segment must start at or after the previous segment ends, and duplicates are not allowed
+            if entries.last().is_some_and(|(previous, _)| (segment.lower() < previous.upper()) || (&segment == previous)) {
+                panic!("segments must not overlap");
+            }
+            entries.push((segment, value));
+        }
+        SegmentMap::from_sorted(entries)
+    }
+
     pub fn segments(&self) -> Segments<'_, K, V> {
         Segments { inner: self.iter() }
     }
 
-    pub fn values(&self) -> Values<'_, K, V> {
-        Values { inner: self.iter() }
+    pub fn boundaries(&self) -> Boundaries<'_, K, V> {
+        Boundaries {
+            inner: self.iter(),
+            pending: None,
+            last: None,
+        }
+    }
+
+    pub fn zip_with<'a, W, F, T>(&'a self, other: &'a SegmentMap<K, W>, f: F) -> ZipWith<'a, K, V, W, F>
+    where
+        F: FnMut(Option<&'a V>, Option<&'a W>) -> T
+    {
+        ZipWith {
+            left: self.iter().peekable(),
+            right: other.iter().peekable(),
+            position: None,
+            f,
+        }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            current: self.root.as_ref(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn iter_flatten<'a, T>(&'a self) -> impl Iterator<Item = (&'a Segment<K>, &'a T)>
+    where
+        &'a V: IntoIterator<Item = &'a T>,
+        T: 'a,
+    {
+        self.iter().flat_map(|(segment, value)| value.into_iter().map(move |element| (segment, element)))
+    }
+
+    pub fn iter_from(&self, key: &K) -> Iter<'_, K, V> {
+        let mut stack = Vec::new();
+        let mut current = self.root.as_ref();
+        while let Some(node) = current.take() {
+            // if self segment ends after key, it will be yielded, so descend left
+            if node.segment.upper() > key {
+                stack.push((&node.segment, &node.value, (*node.right).as_ref()));
+                current = (*node.left).as_ref();
+            // otherwise, self segment and left are skipped, so descend right
+            } else {
+                current = (*node.right).as_ref();
+            }
+        }
+        Iter {
+            current: None,
+            stack,
+        }
+    }
+
+    /// Splits the entries into those starting below `key` and those starting at or
+    /// above it, without cloning. A segment straddling `key` is not clipped; it appears
+    /// whole in the lower iterator only.
+    pub fn split_view<'a>(&'a self, key: &'a K) -> (IterBelow<'a, K, V>, Iter<'a, K, V>) {
+        let lower = IterBelow {
+            inner: Some(self.iter()),
+            key,
+        };
+        let mut stack = Vec::new();
+        let mut current = self.root.as_ref();
+        while let Some(node) = current.take() {
+            // if self segment starts at or after key, it will be yielded, so descend left
+            if node.segment.lower() >= key {
+                stack.push((&node.segment, &node.value, (*node.right).as_ref()));
+                current = (*node.left).as_ref();
+            // otherwise, self segment and left are skipped, so descend right
+            } else {
+                current = (*node.right).as_ref();
+            }
+        }
+        let upper = Iter {
+            current: None,
+            stack,
+        };
+        (lower, upper)
+    }
+
+    pub fn cursor(&self) -> Cursor<'_, K, V> {
+        let mut cursor = Cursor {
+            root: self.root.as_ref(),
+            path: Vec::new(),
+        };
+        cursor.move_next();
+        cursor
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            current: self.root.as_mut(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn span(&self) -> Option<Segment<&K>> {
+        self.root.as_ref().map(|root| root.span())
+    }
+
+    pub fn min_segment(&self) -> Option<&Segment<K>> {
+        self.root.as_ref().map(|root| &root.min_node().segment)
+    }
+
+    pub fn min_value(&self) -> Option<&V> {
+        self.root.as_ref().map(|root| &root.min_node().value)
+    }
+
+    pub fn max_segment(&self) -> Option<&Segment<K>> {
+        self.root.as_ref().map(|root| &root.max_node().segment)
+    }
+
+    pub fn max_value(&self) -> Option<&V> {
+        self.root.as_ref().map(|root| &root.max_node().value)
+    }
+
+    pub fn pop_min(&mut self) -> Option<(Segment<K>, V)> {
+        self.pop_min_with(drop)
+    }
+
+    pub(crate) fn pop_min_with<F>(&mut self, mut recycle: F) -> Option<(Segment<K>, V)>
+    where
+        F: FnMut(Box<Option<SegmentMapNode<K, V>>>)
+    {
+        let (root, SegmentMapNode { segment, value, left, right, .. }) = self.root.take()?.remove_min_node();
+        self.root = root;
+        recycle(left);
+        recycle(right);
+        Some((segment, value))
+    }
+
+    pub fn pop_max(&mut self) -> Option<(Segment<K>, V)> {
+        self.pop_max_with(drop)
+    }
+
+    pub(crate) fn pop_max_with<F>(&mut self, mut recycle: F) -> Option<(Segment<K>, V)>
+    where
+        F: FnMut(Box<Option<SegmentMapNode<K, V>>>)
+    {
+        let (root, SegmentMapNode { segment, value, left, right, .. }) = self.root.take()?.remove_max_node();
+        self.root = root;
+        recycle(left);
+        recycle(right);
+        Some((segment, value))
+    }
+
+    pub fn len(&self) -> usize {
+        SegmentMapNode::size_of(&self.root)
+    }
+
+    /// Counts the segments lying entirely below `key`, i.e. ending at or before it.
+    pub fn rank(&self, key: &K) -> usize {
+        let mut rank = 0;
+        let mut current = self.root.as_ref();
+        while let Some(node) = current {
+            // if node segment ends at or before key, it and its left are below key
+            if node.segment.upper() <= key {
+                rank += SegmentMapNode::size_of(&node.left) + 1;
+                current = (*node.right).as_ref();
+            // otherwise, only segments on the left may be below key
+            } else {
+                current = (*node.left).as_ref();
+            }
+        }
+        rank
+    }
+
+    /// Returns the entry at position `index` in order.
+    pub fn select(&self, mut index: usize) -> Option<(&Segment<K>, &V)> {
+        let mut current = self.root.as_ref();
+        while let Some(node) = current {
+            let left_size = SegmentMapNode::size_of(&node.left);
+            // if index is within left, descend left
+            if index < left_size {
+                current = (*node.left).as_ref();
+            // if index is node itself
+            } else if index == left_size {
+                return Some((&node.segment, &node.value));
+            // otherwise, skip left and node, descend right
+            } else {
+                index -= left_size + 1;
+                current = (*node.right).as_ref();
+            }
+        }
+        // index is out of bounds
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.clear_with(drop);
+    }
+
+    /// Does nothing, since nodes are freed as soon as they are removed. Provided for
+    /// parity with the standard collections.
+    pub fn shrink_to_fit(&mut self) {}
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.root.as_ref().and_then(|root| root.get(key))
+    }
+
+    pub fn get_copied(&self, key: &K) -> Option<V>
+    where
+        V: Copy
+    {
+        self.get(key).copied()
+    }
+
+    pub fn get_segment_copied(&self, key: &K) -> Option<Segment<K>>
+    where
+        K: Copy
+    {
+        self.get_entry(key).map(|(segment, _)| *segment)
+    }
+
+    pub fn get_entry(&self, key: &K) -> Option<(&Segment<K>, &V)> {
+        self.root.as_ref().and_then(|root| root.get_entry(key))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn context(&self, key: &K) -> (Option<(&Segment<K>, &V)>, Option<(&Segment<K>, &V)>, Option<(&Segment<K>, &V)>) {
+        let mut prev = None;
+        let mut next = None;
+        let mut current = self.root.as_ref();
+        while let Some(node) = current {
+            // if node segment contains key, neighbors are the closest on either side
+            if node.segment.contains(key) {
+                if let Some(left) = node.left.as_ref() {
+                    prev = Some(left.max_node());
+                }
+                if let Some(right) = node.right.as_ref() {
+                    next = Some(right.min_node());
+                }
+                return (prev.map(SegmentMapNode::entry), Some(node.entry()), next.map(SegmentMapNode::entry));
+            // if key is less than node segment, node is the closest next so far
+            } else if key < node.segment.lower() {
+                next = Some(node);
+                current = (*node.left).as_ref();
+            // otherwise, key is greater than node segment, node is the closest prev so far
+            } else {
+                prev = Some(node);
+                current = (*node.right).as_ref();
+            }
+        }
+        // key is in a gap
+        (prev.map(SegmentMapNode::entry), None, next.map(SegmentMapNode::entry))
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get_entry(key).is_some()
+    }
+
+    /// Looks up the entry containing each key. Ascending keys share a single traversal;
+    /// a key smaller than its predecessor restarts the traversal from that key.
+    pub fn get_entries_sorted<'a, I>(&self, keys: I) -> Vec<Option<(&Segment<K>, &V)>>
+    where
+        I: IntoIterator<Item = &'a K>,
+        K: 'a,
+    {
+        let mut entries = Vec::new();
+        let mut previous: Option<&K> = None;
+        let mut iter: Option<Peekable<Iter<'_, K, V>>> = None;
+        for key in keys {
+            // if keys descend, the traversal can't be reused
+            if previous.is_none_or(|previous| key < previous) {
+                iter = Some(self.iter_from(key).peekable());
+            }
+            previous = Some(key);
+            let iter = iter.as_mut().expect("traversal exists");
+            // skip segments ending at or before key
+            while iter.next_if(|(segment, _)| segment.upper() <= key).is_some() {}
+            entries.push(iter.peek().copied().filter(|(segment, _)| segment.contains(key)));
+        }
+        entries
+    }
+
+    pub fn replace_value(&mut self, key: &K, value: V) -> Option<V> {
+        self.root.as_mut()
+            .and_then(|root| root.get_mut(key))
+            .map(|current| std::mem::replace(current, value))
+    }
+
+    pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        // every key must exist and no two keys may resolve to the same segment
+        for (i, key) in keys.iter().enumerate() {
+            let (segment, _) = self.get_entry(key)?;
+            if keys[i + 1..].iter().any(|other| segment.contains(other)) {
+                return None;
+            }
+        }
+        let mut values: [Option<&mut V>; N] = [(); N].map(|_| None);
+        let mut remaining = N;
+        for (segment, value) in self.iter_mut() {
+            // stop once every key has been resolved
+            if remaining == 0 {
+                break;
+            } else if let Some(i) = keys.iter().position(|key| segment.contains(key)) {
+                values[i] = Some(value);
+                remaining -= 1;
+            }
+        }
+        Some(values.map(|value| value.expect("key exists")))
+    }
+
+    pub fn overlaps(&self, segment: &Segment<K>) -> bool {
+        self.root.as_ref().is_some_and(|root| root.overlaps(segment))
+    }
+
+    pub fn retain_overlapping<I>(&mut self, windows: I)
+    where
+        I: IntoIterator<Item = Segment<K>>
+    {
+        let windows = windows.into_iter().collect::<Vec<_>>();
+        let entries = std::mem::take(self).into_iter()
+            .filter(|(segment, _)| windows.iter().any(|window| (segment.lower() < window.upper()) && (window.lower() < segment.upper())))
+            .collect();
+        *self = SegmentMap::from_sorted(entries);
+    }
+
+    pub fn retain_map<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Segment<K>, &mut V) -> bool
+    {
+        let entries = std::mem::take(self).into_iter()
+            .filter_map(|(segment, mut value)| if f(&segment, &mut value) { Some((segment, value)) } else { None })
+            .collect();
+        *self = SegmentMap::from_sorted(entries);
+    }
+
+    pub fn validate(&self) -> bool {
+        let mut segments = self.segments();
+        let mut previous = match segments.next() {
+            Some(first) => first,
+            None => return true,
+        };
+        if previous.lower() > previous.upper() {
+            return false;
+        }
+        for segment in segments {
+            // segments must be well-formed, ordered, disjoint, and not duplicated
+            if (segment.lower() > segment.upper()) || (previous.upper() > segment.lower()) || (previous == segment) {
+                return false;
+            }
+            previous = segment;
+        }
+        // every node size must count itself and its children
+        let mut stack = self.root.iter().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if node.size != 1 + SegmentMapNode::size_of(&node.left) + SegmentMapNode::size_of(&node.right) {
+                return false;
+            }
+            stack.extend(node.left.iter());
+            stack.extend(node.right.iter());
+        }
+        true
+    }
+
+    pub fn covers_same(&self, other: &SegmentMap<K, V>) -> bool {
+        self.coverage().eq(other.coverage())
+    }
+
+    fn coverage(&self) -> impl Iterator<Item = (&K, &K)> {
+        let mut segments = self.segments().filter(|segment| !segment.is_empty()).peekable();
+        std::iter::from_fn(move || {
+            let first = segments.next()?;
+            let mut upper = first.upper();
+            // touching segments are continuous coverage
+            while let Some(next) = segments.next_if(|next| next.lower() == upper) {
+                upper = next.upper();
+            }
+            Some((first.lower(), upper))
+        })
+    }
+
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        let mut stack = self.root.iter().map(|root| (root, 0)).collect::<Vec<_>>();
+        while let Some((node, depth)) = stack.pop() {
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+            histogram[depth] += 1;
+            stack.extend(node.left.iter().map(|left| (left, depth + 1)));
+            stack.extend(node.right.iter().map(|right| (right, depth + 1)));
+        }
+        histogram
+    }
+
+    pub fn insert(&mut self, segment: Segment<K>, value: V) {
+        if let Some(root) = self.root.as_mut() {
+            root.insert(segment, value);
+        } else {
+            self.root = Some(SegmentMapNode::new(segment, value, None, None));
+        }
+    }
+
+    pub fn try_insert(&mut self, segment: Segment<K>, value: V) -> Result<(), OverlapError<K, V>> {
+        self.try_insert_with(segment, value, |segment, value| SegmentMapNode::new(segment, value, None, None))
+    }
+
+    pub(crate) fn try_insert_with<F>(&mut self, segment: Segment<K>, value: V, new: F) -> Result<(), OverlapError<K, V>>
+    where
+        F: FnOnce(Segment<K>, V) -> SegmentMapNode<K, V>
+    {
+        if let Some(root) = self.root.as_mut() {
+            root.try_insert_with(segment, value, new)
+        } else {
+            self.root = Some(new(segment, value));
+            Ok(())
+        }
+    }
+
+    pub fn insert_strict(&mut self, segment: Segment<K>, value: V) -> Result<(), AdjacentError<K, V>>
+    where
+        V: PartialEq
+    {
+        // empty segments are never adjacent
+        if !segment.is_empty() {
+            let left = self.root.as_ref().and_then(|root| root.get_entry_ending_at(segment.lower()));
+            let right = self.get_entry(segment.upper()).filter(|(right, _)| right.lower() == segment.upper());
+            // if either neighbor touches with an equal value, reject
+            if left.into_iter().chain(right).any(|(_, neighbor_value)| *neighbor_value == value) {
+                return Err(AdjacentError::new(segment, value));
+            }
+        }
+        self.insert(segment, value);
+        Ok(())
+    }
+
+    pub fn extend_balanced(&mut self, mut other: SegmentMap<K, V>) {
+        let root = match (self.root.take(), other.root.take()) {
+            (None, root) | (root, None) => root,
+            (Some(root), Some(other_root)) => {
+                // if all of self precedes other, join with other minimum as the new root
+                if SegmentMap::precedes(&root, &other_root) {
+                    Some(SegmentMap::join(root, other_root))
+                // if all of other precedes self, join with self minimum as the new root
+                } else if SegmentMap::precedes(&other_root, &root) {
+                    Some(SegmentMap::join(other_root, root))
+                // otherwise, ranges interleave, so insert each entry
+                } else {
+                    self.root = Some(root);
+                    self.extend(SegmentMap { root: Some(other_root) });
+                    return;
+                }
+            },
+        };
+        self.root = root;
+    }
+
+    fn precedes(lower: &SegmentMapNode<K, V>, upper: &SegmentMapNode<K, V>) -> bool {
+        let max = &lower.max_node().segment;
+        let min = &upper.min_node().segment;
+        // identical empty segments at the boundary would be duplicates
+        (max.upper() < min.lower()) || ((max.upper() == min.lower()) && (max != min))
+    }
+
+    // joins two trees where all of lower precedes upper. The heavier tree's inner spine is
+    // descended until the subtree there is comparable to the lighter tree, which is joined in
+    // place, and any node on the way back up left out of balance is rebuilt
+    fn join(lower: SegmentMapNode<K, V>, upper: SegmentMapNode<K, V>) -> SegmentMapNode<K, V> {
+        let mut spine = Vec::new();
+        // if upper outweighs lower, descend left spine of upper with lower maximum as the pivot
+        if SegmentMap::<K, V>::outweighs(upper.size, lower.size) {
+            let mut cut = Some(upper);
+            while let Some(mut node) = cut.take_if(|node| SegmentMap::<K, V>::outweighs(node.size, lower.size)) {
+                cut = node.left.take();
+                spine.push(node);
+            }
+            let (lower, pivot) = lower.remove_max_node();
+            let mut joined = SegmentMap::rebalanced(SegmentMapNode::new(pivot.segment, pivot.value, lower, cut));
+            while let Some(mut node) = spine.pop() {
+                node.left = Box::new(Some(joined));
+                joined = SegmentMap::rebalanced(node.resized());
+            }
+            joined
+        // otherwise, descend right spine of lower with upper minimum as the pivot
+        } else {
+            let mut cut = Some(lower);
+            while let Some(mut node) = cut.take_if(|node| SegmentMap::<K, V>::outweighs(node.size, upper.size)) {
+                cut = node.right.take();
+                spine.push(node);
+            }
+            let (upper, pivot) = upper.remove_min_node();
+            let mut joined = SegmentMap::rebalanced(SegmentMapNode::new(pivot.segment, pivot.value, cut, upper));
+            while let Some(mut node) = spine.pop() {
+                node.right = Box::new(Some(joined));
+                joined = SegmentMap::rebalanced(node.resized());
+            }
+            joined
+        }
+    }
+
+    fn outweighs(size: usize, other: usize) -> bool {
+        size + 1 > 3 * (other + 1)
+    }
+
+    // rebuilds node if one side outweighs the other
+    fn rebalanced(node: SegmentMapNode<K, V>) -> SegmentMapNode<K, V> {
+        let left = SegmentMapNode::size_of(&node.left);
+        let right = SegmentMapNode::size_of(&node.right);
+        // if balanced, keep as is
+        if !SegmentMap::<K, V>::outweighs(left, right) && !SegmentMap::<K, V>::outweighs(right, left) {
+            node
+        // otherwise, rebuild from sorted entries
+        } else {
+            let len = node.size;
+            SegmentMapNode::from_sorted(&mut SegmentMap { root: Some(node) }.into_iter(), len).expect("node is not empty")
+        }
+    }
+
+    pub fn try_from_iter<I>(iter: I) -> Result<SegmentMap<K, V>, Vec<OverlapError<K, V>>>
+    where
+        I: IntoIterator<Item = (Segment<K>, V)>
+    {
+        let mut segment_map = SegmentMap::new();
+        let errors = iter.into_iter()
+            .filter_map(|(segment, value)| segment_map.try_insert(segment, value).err())
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(segment_map)
+        } else { Err(errors) }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Bounded + PartialOrd
+{
+    /// Creates a map covering `Segment::all()` with `value`. Note that, since segments are
+    /// half-open, `K::max()` itself is not covered.
+    pub fn full(value: V) -> SegmentMap<K, V> {
+        SegmentMap { root: Some(SegmentMapNode::new(Segment::all(), value, None, None)) }
+    }
+}
+
+impl<K, V> Default for SegmentMap<K, V>
+where
+    K: PartialOrd
+{
+    fn default() -> SegmentMap<K, V> {
+        SegmentMap::new()
+    }
+}
+
+impl<K, V> SegmentMap<K, V> 
+where
+    K: Clone + PartialOrd,
+    V: Clone,
+{
+    pub fn remove(&mut self, segment: &Segment<K>) {
+        debug_assert!(segment.lower() <= segment.upper(), "segment must not be inverted");
+        // inverted segments are treated as a no-op
+        if segment.lower() > segment.upper() {
+            return;
+        }
+        if let Some(root) = self.root.take() {
+            self.root = root.remove(segment);
+        }
+    }
+
+    pub fn remove_if_present(&mut self, segment: &Segment<K>) -> bool {
+        // only segments touching segment can be affected, so the rest of the map is not scanned
+        let present = self.touching(segment).into_iter().any(|(existing, _)| {
+            if segment.is_empty() {
+                // an empty segment removes its equal, or splits a segment strictly around it
+                (existing == segment) || ((existing.lower() < segment.lower()) && (segment.upper() < existing.upper()))
+            } else if existing.is_empty() {
+                // an empty segment is removed when strictly within segment
+                (segment.lower() < existing.lower()) && (existing.upper() < segment.upper())
+            } else { (existing.lower() < segment.upper()) && (segment.lower() < existing.upper()) }
+        });
+        self.remove(segment);
+        present
+    }
+
+    pub fn insert_or_merge(&mut self, segment: Segment<K>, value: V)
+    where
+        V: PartialEq
+    {
+        // check everything before removing neighbors, so a failed insert leaves the map untouched
+        let can_insert = |segment: &Segment<K>| self.root.as_ref().is_none_or(|root| root.can_insert(segment));
+        if !can_insert(&segment) {
+            panic!("segments must not overlap");
+        }
+        // an empty segment at a boundary would end up inside the merged segment, so that side
+        // is not merged
+        let merge_left = can_insert(&Segment::new(segment.lower().clone(), segment.lower().clone()));
+        let merge_right = can_insert(&Segment::new(segment.upper().clone(), segment.upper().clone()));
+        let mut lower = segment.lower().clone();
+        let mut upper = segment.upper().clone();
+        // empty segments are never merged
+        if !segment.is_empty() {
+            // if left neighbor touches with an equal value, absorb it
+            if let Some((left, _)) = self.root.as_ref()
+                .filter(|_| merge_left)
+                .and_then(|root| root.get_entry_ending_at(segment.lower()))
+                .filter(|(_, left_value)| **left_value == value)
+            {
+                let left = left.clone();
+                lower = left.lower().clone();
+                self.remove(&left);
+            }
+            // if right neighbor touches with an equal value, absorb it
+            if let Some((right, _)) = self.get_entry(segment.upper())
+                .filter(|(right, right_value)| merge_right && (right.lower() == segment.upper()) && (**right_value == value))
+            {
+                let right = right.clone();
+                upper = right.upper().clone();
+                self.remove(&right);
+            }
+        }
+        self.insert(Segment::new(lower, upper), value);
+    }
+
+    pub fn paint(&mut self, segment: Segment<K>, value: V) {
+        self.remove(&segment);
+        self.insert(segment, value);
+    }
+
+    /// Inserts `segment` with `value`, resolving the parts overlapping existing segments with
+    /// `resolve(existing, value)`. Segments are fragmented like `update`, so empty segments
+    /// strictly within `segment` are absorbed, and an empty `segment` within an existing
+    /// segment splits it around an empty segment holding the resolved value.
+    pub fn insert_resolving<F>(&mut self, segment: Segment<K>, value: V, resolve: F)
+    where
+        F: FnMut(&V, &V) -> V
+    {
+        let resolve = RefCell::new(resolve);
+        self.update(&segment, |existing| Some(match existing {
+            Some(existing) => (resolve.borrow_mut())(&existing, &value),
+            None => value.clone(),
+        }));
+    }
+
+    pub fn update<F>(&mut self, segment: &Segment<K>, value: F) 
+    where
+        F: Fn(Option<V>) -> Option<V> + Clone
+    {
+        debug_assert!(segment.lower() <= segment.upper(), "segment must not be inverted");
+        // inverted segments are treated as a no-op
+        if segment.lower() > segment.upper() {
+            return;
+        }
+        if let Some(root) = self.root.take() {
+            self.root = root.update(segment, value);
+        } else if let Some(value) = value(None) {
+            self.insert(segment.clone(), value);
+        }
+    }
+
+    /// Like `update`, but `value` may fail. If it returns an error, the first error is
+    /// propagated and the map is left unchanged.
+    pub fn try_update<E, F>(&mut self, segment: &Segment<K>, value: F) -> Result<(), E>
+    where
+        F: Fn(Option<V>) -> Result<Option<V>, E> + Clone
+    {
+        // snapshot only the entries the update can touch, so an error can restore them
+        let touched = self.touching(segment).into_iter()
+            .map(|(segment, value)| (segment.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        let error = RefCell::new(None);
+        self.update(segment, |current| {
+            if error.borrow().is_some() {
+                return None;
+            }
+            value(current).unwrap_or_else(|e| {
+                *error.borrow_mut() = Some(e);
+                None
+            })
+        });
+        match error.into_inner() {
+            Some(e) => {
+                // clear everything between the outermost touched entries, then restore them
+                let lower = match touched.first() {
+                    Some((first, _)) if first.lower() < segment.lower() => first.lower().clone(),
+                    _ => segment.lower().clone(),
+                };
+                let upper = match touched.last() {
+                    Some((last, _)) if last.upper() > segment.upper() => last.upper().clone(),
+                    _ => segment.upper().clone(),
+                };
+                let span = Segment::new(lower.clone(), upper.clone());
+                self.remove(&span);
+                // empty segments at the span bounds are not strictly within it, but the update
+                // may only have touched those at the segment bounds
+                if &lower == segment.lower() {
+                    self.remove(&Segment::new(lower.clone(), lower.clone()));
+                }
+                if &upper == segment.upper() {
+                    self.remove(&Segment::new(upper.clone(), upper.clone()));
+                }
+                for (segment, value) in touched {
+                    self.insert(segment, value);
+                }
+                Err(e)
+            },
+            None => Ok(()),
+        }
+    }
+
+    // collects the entries overlapping or touching segment, including empty segments at its bounds
+    fn touching(&self, segment: &Segment<K>) -> Vec<(&Segment<K>, &V)> {
+        let mut entries = Vec::new();
+        let mut stack = Vec::new();
+        let mut current = self.root.as_ref();
+        loop {
+            while let Some(node) = current {
+                stack.push(node);
+                // left segments all end before node segment starts
+                current = if node.segment.lower() >= segment.lower() { (*node.left).as_ref() } else { None };
+            }
+            let node = match stack.pop() {
+                Some(node) => node,
+                None => break,
+            };
+            if (node.segment.upper() >= segment.lower()) && (node.segment.lower() <= segment.upper()) {
+                entries.push((&node.segment, &node.value));
+            }
+            // right segments all start after node segment ends
+            current = if node.segment.upper() <= segment.upper() { (*node.right).as_ref() } else { None };
+        }
+        entries
+    }
+
+    pub fn update_entry<F>(&mut self, segment: &Segment<K>, value: F)
+    where
+        F: Fn(&Segment<K>, Option<V>) -> Option<V> + Clone
+    {
+        debug_assert!(segment.lower() <= segment.upper(), "segment must not be inverted");
+        // inverted segments are treated as a no-op
+        if segment.lower() > segment.upper() {
+            return;
+        }
+        if let Some(root) = self.root.take() {
+            self.root = root.update_entry(segment, value);
+        } else if let Some(value) = value(segment, None) {
+            self.insert(segment.clone(), value);
+        }
+    }
+
+    /// Replaces each region in `updates` with its value, like calling `update` for each in
+    /// turn. Updates must be sorted and disjoint. The map is rebuilt in a single O(n + m)
+    /// pass, so this beats repeated `update` calls when there are many regions, but not when
+    /// a few regions touch a large map.
+    pub fn apply_sorted<I>(&mut self, updates: I)
+    where
+        I: IntoIterator<Item = (Segment<K>, V)>
+    {
+        let updates = updates.into_iter().collect::<Vec<_>>();
+        debug_assert!(updates.windows(2).all(|pair| pair[0].0.upper() <= pair[1].0.lower()), "updates must be sorted and disjoint");
+        // collect what remains of each entry outside of the updated regions
+        let mut remaining = Vec::new();
+        let mut regions = updates.iter().map(|(region, _)| region).peekable();
+        for (segment, value) in std::mem::take(self) {
+            // an empty segment remains unless a region equals it or strictly encloses it
+            if segment.is_empty() {
+                while regions.next_if(|region| (region.upper() <= segment.lower()) && (**region != segment)).is_some() {}
+                let absorbed = regions.peek().is_some_and(|region| {
+                    (**region == segment) || ((region.lower() < segment.lower()) && (segment.lower() < region.upper()))
+                });
+                if !absorbed {
+                    remaining.push((segment, value));
+                }
+                continue;
+            }
+            let mut position = segment.lower().clone();
+            while let Some(region) = regions.peek() {
+                // if region is entirely before position, skip it
+                if region.upper() <= &position {
+                    regions.next();
+                // if region is entirely after segment, move on to next segment
+                } else if region.lower() >= segment.upper() {
+                    break;
+                } else {
+                    // if there is a gap before region, it remains
+                    if region.lower() > &position {
+                        remaining.push((Segment::new(position, region.lower().clone()), value.clone()));
+                    }
+                    position = region.upper().clone();
+                    // if region extends beyond segment, it may cover the next segment too
+                    if region.upper() >= segment.upper() {
+                        break;
+                    }
+                    regions.next();
+                }
+            }
+            // if segment extends beyond all regions, the rest remains
+            if &position < segment.upper() {
+                remaining.push((Segment::new(position, segment.upper().clone()), value));
+            }
+        }
+        // interleave remaining entries with updates, both are sorted and disjoint
+        let mut entries = Vec::with_capacity(remaining.len() + updates.len());
+        let mut remaining = remaining.into_iter().peekable();
+        let mut updates = updates.into_iter().peekable();
+        while let (Some((segment, _)), Some((region, _))) = (remaining.peek(), updates.peek()) {
+            if (segment.lower(), segment.upper()) < (region.lower(), region.upper()) {
+                entries.extend(remaining.next());
+            } else {
+                entries.extend(updates.next());
+            }
+        }
+        entries.extend(remaining);
+        entries.extend(updates);
+        *self = SegmentMap::from_sorted(entries);
+    }
+
+    pub fn difference(&self, other: &SegmentMap<K, V>) -> SegmentMap<K, V> {
+        let mut entries = Vec::new();
+        let mut others = other.segments().filter(|other| !other.is_empty()).peekable();
+        for (segment, value) in self.iter() {
+            let mut position = segment.lower();
+            while let Some(other) = others.peek() {
+                // if other is entirely before position, skip it
+                if other.upper() <= position {
+                    others.next();
+                // if other is entirely after segment, move on to next segment
+                } else if other.lower() >= segment.upper() {
+                    break;
+                } else {
+                    // if there is a gap before other, it remains
+                    if other.lower() > position {
+                        entries.push((Segment::new(position.clone(), other.lower().clone()), value.clone()));
+                    }
+                    position = other.upper();
+                    // if other extends beyond segment, it may cover the next segment too
+                    if other.upper() >= segment.upper() {
+                        break;
+                    }
+                    others.next();
+                }
+            }
+            // if segment extends beyond all others, the rest remains
+            if position < segment.upper() {
+                entries.push((Segment::new(position.clone(), segment.upper().clone()), value.clone()));
+            }
+        }
+        SegmentMap::from_sorted(entries)
+    }
+
+    /// Removes and returns the parts of segments within `window`, in order. Empty segments
+    /// strictly within `window` are drained as well, while those at its bounds remain.
+    pub fn drain_range(&mut self, window: Segment<K>) -> impl Iterator<Item = (Segment<K>, V)> {
+        let mut retained = Vec::new();
+        let mut drained = Vec::new();
+        for (segment, value) in std::mem::take(self) {
+            // if segment is entirely outside window, it remains
+            if window.is_empty() || (segment.upper() <= window.lower()) || (segment.lower() >= window.upper()) {
+                retained.push((segment, value));
+                continue;
+            }
+            let mut lower = segment.lower();
+            let mut upper = segment.upper();
+            // if segment straddles window lower, the part before window remains
+            if lower < window.lower() {
+                retained.push((Segment::new(lower.clone(), window.lower().clone()), value.clone()));
+                lower = window.lower();
+            }
+            // if segment straddles window upper, the part after window remains
+            let after = if upper > window.upper() {
+                let after = (Segment::new(window.upper().clone(), upper.clone()), value.clone());
+                upper = window.upper();
+                Some(after)
+            } else { None };
+            drained.push((Segment::new(lower.clone(), upper.clone()), value));
+            retained.extend(after);
+        }
+        *self = SegmentMap::from_sorted(retained);
+        drained.into_iter()
+    }
+
+    pub fn retain_keys_in(&mut self, window: Segment<K>) {
+        let drained = self.drain_range(window).collect();
+        *self = SegmentMap::from_sorted(drained);
+    }
+
+    /// Swaps covered and uncovered regions within `within`. Covered regions become gaps,
+    /// and gaps become segments valued `value`. Empty segments strictly within a former gap
+    /// are removed.
+    pub fn invert(&mut self, within: Segment<K>, value: V) {
+        let (empty, covered): (Vec<_>, Vec<_>) = self.drain_range(within.clone()).partition(|(segment, _)| segment.is_empty());
+        let mut gaps = Vec::new();
+        let mut position = within.lower().clone();
+        for (segment, _) in covered {
+            // if there is a gap before segment, it becomes covered
+            if segment.lower() > &position {
+                gaps.push(Segment::new(position, segment.lower().clone()));
+            }
+            position = segment.upper().clone();
+        }
+        // if coverage ended before within upper, the rest becomes covered
+        if &position < within.upper() {
+            gaps.push(Segment::new(position, within.upper().clone()));
+        }
+        // restore the drained empty segments, except those the gaps would overlap
+        let mut remaining = gaps.iter().peekable();
+        for (segment, empty_value) in empty {
+            while remaining.next_if(|gap| gap.upper() <= segment.lower()).is_some() {}
+            if remaining.peek().is_none_or(|gap| gap.lower() >= segment.lower()) {
+                self.insert(segment, empty_value);
+            }
+        }
+        for gap in gaps {
+            self.insert(gap, value.clone());
+        }
+    }
+
+    /// Cyclically shifts the coverage within `bound` by `by`, wrapping anything shifted past
+    /// `bound` upper around to `bound` lower. A segment crossing the wrap point is split in
+    /// two, and segments straddling `bound` only have their part within it shifted. Empty
+    /// segments from `bound` lower up to (but excluding) `bound` upper are shifted as well.
+    pub fn rotate_within(&mut self, bound: Segment<K>, by: K)
+    where
+        K: Add<Output = K> + Sub<Output = K> + Rem<Output = K>,
+    {
+        let width = bound.upper().clone() - bound.lower().clone();
+        let zero = width.clone() - width.clone();
+        // if bound is empty, there is nothing to rotate
+        if width <= zero {
+            return;
+        }
+        // normalize shift into [0, width), even if it is negative, without exceeding width
+        let mut by = by % width.clone();
+        if by < zero {
+            by = by + width.clone();
+        }
+        // segments starting at least this far into bound wrap around
+        let wrap = width - by.clone();
+        let mut drained = self.drain_range(bound.clone()).collect::<Vec<_>>();
+        // drain_range keeps an empty segment at bound lower, which is shifted as well
+        let (empty, retained): (Vec<_>, Vec<_>) = std::mem::take(self).into_iter()
+            .partition(|(segment, _)| segment.is_empty() && bound.contains(segment.lower()));
+        *self = SegmentMap::from_sorted(retained);
+        drained.extend(empty);
+        for (segment, value) in drained {
+            let length = segment.upper().clone() - segment.lower().clone();
+            // if shifted past bound, wrap around
+            let lower = if segment.lower().clone() - bound.lower().clone() >= wrap {
+                segment.lower().clone() - wrap.clone()
+            } else {
+                segment.lower().clone() + by.clone()
+            };
+            let room = bound.upper().clone() - lower.clone();
+            // if segment crosses the wrap point, split it
+            if length > room {
+                self.insert(Segment::new(lower, bound.upper().clone()), value.clone());
+                self.insert(Segment::new(bound.lower().clone(), bound.lower().clone() + (length - room)), value);
+            } else {
+                self.insert(Segment::new(lower.clone(), lower + length), value);
+            }
+        }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: PartialOrd,
+    V: Ord,
+{
+    pub fn entries_by_value(&self) -> Vec<(&Segment<K>, &V)> {
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|&(_, value)| value);
+        entries
+    }
+
+    pub fn value_count(&self) -> usize {
+        self.values().collect::<BTreeSet<_>>().len()
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Clone + PartialOrd,
+{
+    pub fn merge_adjacent_with<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&V, &V) -> Option<V>
+    {
+        self.merge_adjacent_by(|previous_value, value| match f(previous_value, &value) {
+            Some(merged_value) => {
+                *previous_value = merged_value;
+                None
+            },
+            None => Some(value),
+        });
+    }
+
+    /// Merges touching segments with equal values, returning the number of merges performed.
+    pub fn coalesce(&mut self) -> usize
+    where
+        V: PartialEq
+    {
+        self.merge_adjacent_by(|previous_value, value| if *previous_value == value { None } else { Some(value) })
+    }
+
+    /// Alias of `coalesce` that discards the number of merges.
+    pub fn dedup_values(&mut self)
+    where
+        V: PartialEq
+    {
+        self.coalesce();
+    }
+
+    // merges each segment into the previous one it touches, unless merge hands its value back,
+    // returning the number of merges performed
+    fn merge_adjacent_by<F>(&mut self, mut merge: F) -> usize
+    where
+        F: FnMut(&mut V, V) -> Option<V>
+    {
+        let mut merges = 0;
+        let mut entries: Vec<(Segment<K>, V)> = Vec::new();
+        for (segment, value) in std::mem::take(self) {
+            // if touching previous segment and values merge, extend previous segment
+            let value = match entries.last_mut() {
+                Some((previous_segment, previous_value)) if previous_segment.upper() == segment.lower() => {
+                    match merge(previous_value, value) {
+                        Some(value) => value,
+                        None => {
+                            *previous_segment = Segment::new(previous_segment.lower().clone(), segment.upper().clone());
+                            merges += 1;
+                            continue;
+                        },
+                    }
+                },
+                _ => value,
+            };
+            entries.push((segment, value));
+        }
+        *self = SegmentMap::from_sorted(entries);
+        merges
+    }
+
+    /// Inserts a segment for each gap within `within`, valued by calling `f` with the gap, so
+    /// that `within` becomes fully covered. Existing segments are left untouched, except
+    /// empty segments strictly within a gap, which are removed.
+    pub fn fill_gaps_with<F>(&mut self, within: Segment<K>, mut f: F)
+    where
+        F: FnMut(&Segment<K>) -> V
+    {
+        let mut gaps = Vec::new();
+        let mut absorbed = Vec::new();
+        let mut position = within.lower().clone();
+        for (segment, _) in self.iter_from(within.lower()) {
+            // if within is fully covered, stop
+            if (&position >= within.upper()) || (segment.lower() >= within.upper()) {
+                break;
+            // empty segments do not contribute coverage, but those after position are
+            // strictly within a gap and would overlap its filling
+            } else if segment.is_empty() {
+                if segment.lower() > &position {
+                    absorbed.push(segment.clone());
+                }
+                continue;
+            }
+            // if there is a gap before segment, it must be filled
+            if segment.lower() > &position {
+                gaps.push(Segment::new(position, segment.lower().clone()));
+            }
+            position = segment.upper().clone();
+        }
+        // if coverage ended before within upper, the rest must be filled
+        if &position < within.upper() {
+            gaps.push(Segment::new(position, within.upper().clone()));
+        }
+        // if any empty segments are absorbed, rebuild without them
+        if !absorbed.is_empty() {
+            let mut absorbed = absorbed.into_iter().peekable();
+            let retained = std::mem::take(self).into_iter()
+                .filter(|(segment, _)| absorbed.next_if(|absorbed| absorbed == segment).is_none())
+                .collect();
+            *self = SegmentMap::from_sorted(retained);
+        }
+        for gap in gaps {
+            let value = f(&gap);
+            self.insert(gap, value);
+        }
+    }
+
+    pub fn map_entries<U, F>(&self, mut f: F) -> SegmentMap<K, U>
+    where
+        F: FnMut(&Segment<K>, &V) -> U
+    {
+        let entries = self.iter()
+            .map(|(segment, value)| (segment.clone(), f(segment, value)))
+            .collect();
+        SegmentMap::from_sorted(entries)
+    }
+
+    pub fn scan_values<B, F>(&self, init: B, mut f: F) -> SegmentMap<K, B>
+    where
+        B: Clone,
+        F: FnMut(&B, &V) -> B
+    {
+        let mut accumulator = init;
+        let entries = self.iter()
+            .map(|(segment, value)| {
+                accumulator = f(&accumulator, value);
+                (segment.clone(), accumulator.clone())
+            })
+            .collect();
+        SegmentMap::from_sorted(entries)
+    }
+
+    pub fn clipped_iter(&self, window: Segment<K>) -> impl Iterator<Item = (Segment<K>, &V)> {
+        let upper = window.upper().clone();
+        self.iter_from(window.lower())
+            .take_while(move |(segment, _)| segment.lower() < &upper)
+            .filter_map(move |(segment, value)| segment.clamp_to(&window).map(|segment| (segment, value)))
+    }
+
+    /// Lists the changes between `self` and `newer`, fragmented by the boundaries of both.
+    /// Empty segments map no keys, so they are never reported, even when only one side has
+    /// them.
+    pub fn diff(&self, newer: &SegmentMap<K, V>) -> Vec<Change<K, V>>
+    where
+        V: Clone + PartialEq
+    {
+        self.zip_with(newer, |old, new| (old, new))
+            .filter_map(|(segment, values)| match values {
+                (Some(old), Some(new)) if old != new => Some(Change::Changed(segment, old.clone(), new.clone())),
+                (Some(old), None) => Some(Change::Removed(segment, old.clone())),
+                (None, Some(new)) => Some(Change::Added(segment, new.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Applies changes such as those produced by `diff`, so that `a.apply_changes(a.diff(&b))`
+    /// maps every key the same as `b`. Changed segments may be fragments of stored segments,
+    /// so they are painted rather than replaced in place. Since `diff` ignores empty segments,
+    /// those only in `b` are not added, and those strictly within an added segment are
+    /// dropped.
+    pub fn apply_changes<I>(&mut self, changes: I)
+    where
+        I: IntoIterator<Item = Change<K, V>>,
+        V: Clone
+    {
+        for change in changes {
+            match change {
+                Change::Added(segment, value) | Change::Changed(segment, _, value) => self.paint(segment, value),
+                Change::Removed(segment, _) => self.remove(&segment),
+            }
+        }
+    }
+
+    pub fn resample<I, F>(&self, targets: I, mut pick: F) -> SegmentMap<K, V>
+    where
+        I: IntoIterator<Item = Segment<K>>,
+        F: FnMut(&Segment<K>, &SegmentMap<K, V>) -> Option<V>
+    {
+        // if nothing is picked, target is left uncovered
+        let entries = targets.into_iter()
+            .filter_map(|target| pick(&target, self).map(|value| (target, value)))
+            .collect::<Vec<_>>();
+        // sorted targets build a balanced map directly
+        if SegmentMap::check_sorted(&entries).is_ok() {
+            return SegmentMap::from_sorted(entries);
+        }
+        // otherwise, insert one at a time, which still panics on overlapping targets
+        let mut segment_map = SegmentMap::new();
+        for (target, value) in entries {
+            segment_map.insert(target, value);
+        }
+        segment_map
+    }
+
+    pub fn intersection_with<F>(&self, other: &SegmentMap<K, V>, mut f: F) -> SegmentMap<K, V>
+    where
+        F: FnMut(&V, &V) -> V
+    {
+        let mut entries = Vec::new();
+        let mut lefts = self.iter().filter(|(segment, _)| !segment.is_empty()).peekable();
+        let mut rights = other.iter().filter(|(segment, _)| !segment.is_empty()).peekable();
+        while let (Some((left, left_value)), Some((right, right_value))) = (lefts.peek(), rights.peek()) {
+            // if left is entirely before right, skip it
+            if left.upper() <= right.lower() {
+                lefts.next();
+            // if right is entirely before left, skip it
+            } else if right.upper() <= left.lower() {
+                rights.next();
+            // otherwise, they overlap, so combine the overlap and skip whichever ends first
+            } else {
+                let intersection = left.intersection(right).expect("segments overlap");
+                entries.push((intersection, f(left_value, right_value)));
+                if left.upper() < right.upper() {
+                    lefts.next();
+                } else if right.upper() < left.upper() {
+                    rights.next();
+                } else {
+                    lefts.next();
+                    rights.next();
+                }
+            }
+        }
+        SegmentMap::from_sorted(entries)
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Next,
+    V: PartialEq,
+{
+    pub fn from_step_fn<F>(range: Segment<K>, mut f: F) -> SegmentMap<K, V>
+    where
+        F: FnMut(&K) -> V
+    {
+        let mut entries = Vec::new();
+        let mut run: Option<(K, V)> = None;
+        let mut key = Some(range.lower().clone());
+        while let Some(current) = key.take().filter(|current| current < range.upper()) {
+            let value = f(&current);
+            // if value differs from the current run, close the run and start another
+            if run.as_ref().is_none_or(|(_, run_value)| *run_value != value) {
+                if let Some((run_lower, run_value)) = run.take() {
+                    entries.push((Segment::new(run_lower, current.clone()), run_value));
+                }
+                run = Some((current.clone(), value));
+            }
+            key = current.next_checked();
+        }
+        // close the final run at range upper
+        if let Some((run_lower, run_value)) = run {
+            entries.push((Segment::new(run_lower, range.upper().clone()), run_value));
+        }
+        SegmentMap::from_sorted(entries)
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Next,
+{
+    pub fn for_each_point<F>(&self, window: &Segment<K>, mut f: F)
+    where
+        F: FnMut(&K, &V)
+    {
+        for (segment, value) in self.iter_from(window.lower()) {
+            // if segment starts after window, no later segment is covered
+            if segment.lower() >= window.upper() {
+                break;
+            }
+            let lower = if segment.lower() > window.lower() { segment.lower() } else { window.lower() };
+            let upper = if segment.upper() < window.upper() { segment.upper() } else { window.upper() };
+            let mut key = Some(lower.clone());
+            while let Some(current) = key.take().filter(|current| current < upper) {
+                f(&current, value);
+                key = current.next_checked();
+            }
+        }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: PartialOrd,
+    V: PartialEq,
+{
+    pub fn uniform_value(&self, query: &Segment<K>) -> Option<&V> {
+        let mut position = query.lower();
+        let mut uniform_value = None;
+        for (segment, value) in self.iter_from(query.lower()) {
+            // if query is fully covered, stop
+            if position >= query.upper() {
+                break;
+            // if there is a gap before segment, query is not covered
+            } else if segment.lower() > position {
+                return None;
+            // empty segments do not contribute coverage
+            } else if segment.is_empty() {
+                continue;
+            }
+            // if value differs from the previous value, query is not uniform
+            if uniform_value.is_some_and(|uniform_value| uniform_value != value) {
+                return None;
+            }
+            uniform_value = Some(value);
+            position = segment.upper();
+        }
+        // if coverage ended before query upper, there is a gap
+        if position >= query.upper() {
+            uniform_value
+        } else { None }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Clone + PartialOrd + Measure,
+    K::Output: PartialOrd,
+{
+    pub fn remove_narrow(&mut self, min_width: K::Output) {
+        let entries = std::mem::take(self).into_iter()
+            .filter(|(segment, _)| segment.measure() >= min_width)
+            .collect();
+        *self = SegmentMap::from_sorted(entries);
+    }
+
+    /// The widest contiguous covered sub-segment of `within`, treating touching segments as
+    /// continuous regardless of their values. Ties go to the first run.
+    pub fn longest_run(&self, within: Segment<K>) -> Option<Segment<K>> {
+        let mut longest: Option<Segment<K>> = None;
+        let mut run: Option<Segment<K>> = None;
+        for (segment, _) in self.iter_from(within.lower()) {
+            // if segment starts beyond within, stop
+            if segment.lower() >= within.upper() {
+                break;
+            }
+            // empty segments do not contribute coverage
+            let clipped = match segment.clamp_to(&within) {
+                Some(clipped) => clipped,
+                None => continue,
+            };
+            run = match run {
+                // if touching the current run, extend it
+                Some(current) if current.upper() == clipped.lower() => Some(Segment::new(current.lower().clone(), clipped.upper().clone())),
+                // otherwise, the current run ends and a new one begins
+                current => {
+                    if let Some(current) = current {
+                        if longest.as_ref().is_none_or(|longest| current.measure() > longest.measure()) {
+                            longest = Some(current);
+                        }
+                    }
+                    Some(clipped)
+                },
+            };
+        }
+        // the final run may be the longest
+        if let Some(current) = run {
+            if longest.as_ref().is_none_or(|longest| current.measure() > longest.measure()) {
+                longest = Some(current);
+            }
+        }
+        longest
+    }
+
+    /// Merges consecutive equal-valued segments separated by a gap measuring at most
+    /// `max_gap`. The filled gap adopts the shared value.
+    pub fn coalesce_within(&mut self, max_gap: K::Output)
+    where
+        V: PartialEq
+    {
+        let mut entries: Vec<(Segment<K>, V)> = Vec::new();
+        for (segment, value) in std::mem::take(self) {
+            // if near previous segment with an equal value, extend previous segment over the gap
+            if let Some((previous_segment, previous_value)) = entries.last_mut() {
+                if (*previous_value == value) && (K::measure(previous_segment.upper(), segment.lower()) <= max_gap) {
+                    *previous_segment = Segment::new(previous_segment.lower().clone(), segment.upper().clone());
+                    continue;
+                }
+            }
+            entries.push((segment, value));
+        }
+        *self = SegmentMap::from_sorted(entries);
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Clone + PartialOrd + Measure,
+    K::Output: Sum,
+{
+    pub fn covered_length_in(&self, within: &Segment<K>) -> K::Output {
+        self.iter_from(within.lower())
+            .take_while(|(segment, _)| segment.lower() < within.upper())
+            .filter_map(|(segment, _)| segment.intersection(within))
+            .map(|intersection| intersection.measure())
+            .sum()
+    }
+
+    pub fn coverage_ratio(&self, within: &Segment<K>) -> f64 {
+        let total = K::to_f64(within.measure());
+        // if window is empty, nothing is covered
+        if total == 0.0 {
+            0.0
+        } else { K::to_f64(self.covered_length_in(within)) / total }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Clone + PartialOrd + Sub<Output = K>,
+    usize: TryFrom<K>,
+{
+    pub fn point_count(&self) -> usize {
+        self.segments()
+            .map(|segment| usize::try_from(segment.upper().clone() - segment.lower().clone()).ok().expect("width must fit in usize"))
+            .sum()
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: PartialOrd + Measure,
+    K::Output: Add<Output = K::Output> + PartialOrd,
+    V: Ord,
+{
+    pub fn dominant_value(&self) -> Option<(&V, K::Output)> {
+        // total measure of each value, alongside the position it first occurs
+        let mut totals: BTreeMap<&V, (usize, K::Output)> = BTreeMap::new();
+        for (position, (segment, value)) in self.iter().enumerate() {
+            let total = match totals.remove(value) {
+                Some((first, total)) => (first, total + segment.measure()),
+                None => (position, segment.measure()),
+            };
+            totals.insert(value, total);
+        }
+        let mut dominant: Option<(&V, usize, K::Output)> = None;
+        for (value, (first, total)) in totals {
+            // ties are broken by first occurrence
+            let replace = dominant.as_ref().is_none_or(|(_, dominant_first, dominant_total)| {
+                (total > *dominant_total) || ((total == *dominant_total) && (first < *dominant_first))
+            });
+            if replace {
+                dominant = Some((value, first, total));
+            }
+        }
+        dominant.map(|(value, _, total)| (value, total))
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Clone + Ord,
+{
+    /// Converts to a map from each segment lower to its upper and value. An empty segment
+    /// sharing its lower with the following segment, e.g. `[6, 6)` before `[6, 12)`, collides
+    /// on that key and is dropped in favor of the following segment.
+    pub fn to_btreemap(&self) -> BTreeMap<K, (K, V)>
+    where
+        V: Clone
+    {
+        self.iter()
+            .map(|(segment, value)| (segment.lower().clone(), (segment.upper().clone(), value.clone())))
+            .collect()
+    }
+
+    pub fn from_btreemap(btreemap: BTreeMap<K, (K, V)>) -> SegmentMap<K, V> {
+        let len = btreemap.len();
+        let mut previous_upper: Option<K> = None;
+        let mut entries = btreemap.into_iter().map(|(lower, (upper, value))| {
+            // entries are sorted by lower, so only neighbors can overlap
+            if let Some(previous_upper) = previous_upper.as_ref() {
+                if previous_upper > &lower {
+                    panic!("segments must not overlap");
+                }
+            }
+            previous_upper = Some(upper.clone());
+            (Segment::new(lower, upper), value)
+        });
+        SegmentMap { root: SegmentMapNode::from_sorted(&mut entries, len) }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: Clone + PartialOrd,
+    V: Clone,
+{
+    pub fn iter_cloned(&self) -> IterCloned<'_, K, V> {
+        IterCloned { inner: self.iter() }
+    }
+}
+
+pub struct Segments<'a, K, V> {
+    inner: Iter<'a, K, V>
+}
+
+impl<'a, K, V> Iterator for Segments<'a, K, V> {
+    type Item = &'a Segment<K>;
+
+    fn next(&mut self) -> Option<&'a Segment<K>> {
+        self.inner.next().map(|(segment, _)| segment)
+    }
+}
+
+pub struct Boundaries<'a, K, V> {
+    inner: Iter<'a, K, V>,
+    pending: Option<&'a K>,
+    last: Option<&'a K>,
+}
+
+impl<'a, K, V> Iterator for Boundaries<'a, K, V>
+where
+    K: PartialOrd
+{
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        loop {
+            // if upper of previous segment is pending and distinct, yield it
+            if let Some(upper) = self.pending.take() {
+                if self.last != Some(upper) {
+                    self.last = Some(upper);
+                    return Some(upper);
+                }
+            }
+            let (segment, _) = self.inner.next()?;
+            self.pending = Some(segment.upper());
+            // if lower is distinct from the last boundary, yield it
+            if self.last != Some(segment.lower()) {
+                self.last = Some(segment.lower());
+                return Some(segment.lower());
+            }
+        }
+    }
+}
+
+pub struct ZipWith<'a, K, V, W, F> {
+    left: Peekable<Iter<'a, K, V>>,
+    right: Peekable<Iter<'a, K, W>>,
+    position: Option<K>,
+    f: F,
+}
+
+impl<'a, K, V, W, F, T> Iterator for ZipWith<'a, K, V, W, F>
+where
+    K: Clone + PartialOrd,
+    F: FnMut(Option<&'a V>, Option<&'a W>) -> T,
+{
+    type Item = (Segment<K>, T);
+
+    fn next(&mut self) -> Option<(Segment<K>, T)> {
+        let position = self.position.take();
+        let is_done = |segment: &Segment<K>| segment.is_empty() || position.as_ref().is_some_and(|position| segment.upper() <= position);
+        // skip empty segments and segments entirely before position
+        while self.left.next_if(|(segment, _)| is_done(segment)).is_some() {}
+        while self.right.next_if(|(segment, _)| is_done(segment)).is_some() {}
+        let left = self.left.peek().copied();
+        let right = self.right.peek().copied();
+        // each side starts at its lower, or at position if already partially yielded
+        let start_of = |segment: &Segment<K>| match position.as_ref() {
+            Some(position) if position > segment.lower() => position.clone(),
+            _ => segment.lower().clone(),
+        };
+        let left_start = left.map(|(segment, _)| start_of(segment));
+        let right_start = right.map(|(segment, _)| start_of(segment));
+        let start = match (left_start.as_ref(), right_start.as_ref()) {
+            (Some(left_start), Some(right_start)) => if left_start <= right_start { left_start.clone() } else { right_start.clone() },
+            (Some(left_start), None) => left_start.clone(),
+            (None, Some(right_start)) => right_start.clone(),
+            (None, None) => return None,
+        };
+        // a side is active if it starts at start, in which case it ends at its upper, otherwise it bounds the piece at its start
+        let left_value = left.filter(|_| left_start.as_ref() == Some(&start)).map(|(_, value)| value);
+        let right_value = right.filter(|_| right_start.as_ref() == Some(&start)).map(|(_, value)| value);
+        let left_end = left.map(|(segment, _)| if left_value.is_some() { segment.upper().clone() } else { start_of(segment) });
+        let right_end = right.map(|(segment, _)| if right_value.is_some() { segment.upper().clone() } else { start_of(segment) });
+        let end = match (left_end, right_end) {
+            (Some(left_end), Some(right_end)) => if left_end <= right_end { left_end } else { right_end },
+            (Some(left_end), None) => left_end,
+            (None, Some(right_end)) => right_end,
+            (None, None) => unreachable!(),
+        };
+        self.position = Some(end.clone());
+        Some((Segment::new(start, end), (self.f)(left_value, right_value)))
+    }
+}
+
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+}
+
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+}
+
+pub struct IterCloned<'a, K, V> {
+    inner: Iter<'a, K, V>
+}
+
+impl<'a, K, V> Iterator for IterCloned<'a, K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    type Item = (Segment<K>, V);
+
+    fn next(&mut self) -> Option<(Segment<K>, V)> {
+        self.inner.next().map(|(segment, value)| (segment.clone(), value.clone()))
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub struct Iter<'a, K, V> {
+    current: Option<&'a SegmentMapNode<K, V>>,
+    stack: Vec<(&'a Segment<K>, &'a V, Option<&'a SegmentMapNode<K, V>>)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a Segment<K>, &'a V);
+
+    fn next(&mut self) -> Option<(&'a Segment<K>, &'a V)> {
+        while let Some(current) = self.current.take() {
+            self.stack.push((&current.segment, &current.value, (*current.right).as_ref()));
+            self.current = (*current.left).as_ref();
+        }
+        if let Some((segment, value, right)) = self.stack.pop() {
+            self.current = right;
+            Some((segment, value))
+        } else { None }
+    }
+}
+
+pub struct IterBelow<'a, K, V> {
+    inner: Option<Iter<'a, K, V>>,
+    key: &'a K,
+}
+
+impl<'a, K, V> Iterator for IterBelow<'a, K, V>
+where
+    K: PartialOrd
+{
+    type Item = (&'a Segment<K>, &'a V);
+
+    fn next(&mut self) -> Option<(&'a Segment<K>, &'a V)> {
+        let (segment, value) = self.inner.as_mut()?.next()?;
+        // segments are sorted, so once one starts at or after key, every later one does too
+        if segment.lower() < self.key {
+            Some((segment, value))
+        } else {
+            self.inner = None;
+            None
+        }
+    }
+}
+
+pub struct Cursor<'a, K, V> {
+    root: Option<&'a SegmentMapNode<K, V>>,
+    path: Vec<&'a SegmentMapNode<K, V>>,
+}
+
+impl<'a, K, V> Cursor<'a, K, V> {
+    pub fn current(&self) -> Option<(&'a Segment<K>, &'a V)> {
+        self.path.last().map(|node| (&node.segment, &node.value))
+    }
+
+    pub fn peek_next(&self) -> Option<(&'a Segment<K>, &'a V)> {
+        let mut cursor = self.clone();
+        cursor.move_next();
+        cursor.current()
+    }
+
+    pub fn peek_prev(&self) -> Option<(&'a Segment<K>, &'a V)> {
+        let mut cursor = self.clone();
+        cursor.move_prev();
+        cursor.current()
+    }
+
+    pub fn move_next(&mut self) {
+        // if before the first entry, descend to the minimum
+        if let Some(current) = self.path.last().copied() {
+            // if right exists, successor is its minimum
+            if let Some(right) = (*current.right).as_ref() {
+                self.push_min(right);
+            // otherwise, successor is the first ancestor reached from its left
+            } else {
+                while let Some(child) = self.path.pop() {
+                    if let Some(parent) = self.path.last() {
+                        if (*parent.left).as_ref().is_some_and(|left| std::ptr::eq(left, child)) {
+                            break;
+                        }
+                    }
+                }
+            }
+        } else if let Some(root) = self.root {
+            self.push_min(root);
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        // if after the last entry, descend to the maximum
+        if let Some(current) = self.path.last().copied() {
+            // if left exists, predecessor is its maximum
+            if let Some(left) = (*current.left).as_ref() {
+                self.push_max(left);
+            // otherwise, predecessor is the first ancestor reached from its right
+            } else {
+                while let Some(child) = self.path.pop() {
+                    if let Some(parent) = self.path.last() {
+                        if (*parent.right).as_ref().is_some_and(|right| std::ptr::eq(right, child)) {
+                            break;
+                        }
+                    }
+                }
+            }
+        } else if let Some(root) = self.root {
+            self.push_max(root);
+        }
+    }
+
+    fn push_min(&mut self, mut node: &'a SegmentMapNode<K, V>) {
+        self.path.push(node);
+        while let Some(left) = (*node.left).as_ref() {
+            self.path.push(left);
+            node = left;
+        }
+    }
+
+    fn push_max(&mut self, mut node: &'a SegmentMapNode<K, V>) {
+        self.path.push(node);
+        while let Some(right) = (*node.right).as_ref() {
+            self.path.push(right);
+            node = right;
+        }
+    }
+}
+
+impl<'a, K, V> Clone for Cursor<'a, K, V> {
+    fn clone(&self) -> Cursor<'a, K, V> {
+        Cursor {
+            root: self.root,
+            path: self.path.clone(),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub struct IterMut<'a, K, V> {
+    current: Option<&'a mut SegmentMapNode<K, V>>,
+    stack: Vec<(&'a Segment<K>, &'a mut V, Option<&'a mut SegmentMapNode<K, V>>)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a Segment<K>, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a Segment<K>, &'a mut V)> {
+        while let Some(current) = self.current.take() {
+            self.stack.push((&current.segment, &mut current.value, (*current.right).as_mut()));
+            self.current = (*current.left).as_mut();
+        }
+        if let Some((segment, value, right)) = self.stack.pop() {
+            self.current = right;
+            Some((segment, value))
+        } else { None }
+    }
+}
+
+impl<K, V> fmt::Debug for SegmentMap<K, V>
+where
+    K: fmt::Debug + PartialOrd,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// comparisons are by in-order entries, so maps with equal contents but different tree
+// shapes compare equal
+impl<K, V> PartialEq for SegmentMap<K, V>
+where
+    K: PartialOrd,
+    V: PartialEq,
+{
+    fn eq(&self, other: &SegmentMap<K, V>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<K, V> Eq for SegmentMap<K, V>
+where
+    K: Eq + PartialOrd,
+    V: Eq,
+{}
+
+impl<K, V> PartialOrd for SegmentMap<K, V>
+where
+    K: PartialOrd,
+    V: PartialOrd,
+{
+    fn partial_cmp(&self, other: &SegmentMap<K, V>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K, V> Ord for SegmentMap<K, V>
+where
+    K: Ord,
+    V: Ord,
+{
+    fn cmp(&self, other: &SegmentMap<K, V>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K, V> Hash for SegmentMap<K, V>
+where
+    K: Hash + PartialOrd,
+    V: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, V> SegmentMap<K, V> {
+    pub(crate) fn clear_with<F>(&mut self, mut recycle: F)
+    where
+        F: FnMut(Box<Option<SegmentMapNode<K, V>>>)
+    {
+        // dismantle nodes iteratively so degenerate trees don't overflow the stack
+        let mut stack = self.root.take().into_iter().collect::<Vec<_>>();
+        while let Some(SegmentMapNode { mut left, mut right, .. }) = stack.pop() {
+            stack.extend(left.take());
+            stack.extend(right.take());
+            recycle(left);
+            recycle(right);
+        }
+    }
+}
+
+impl<K, V> Drop for SegmentMap<K, V> {
+    fn drop(&mut self) {
+        self.clear_with(drop);
+    }
+}
+
+impl<K, V> Extend<(Segment<K>, V)> for SegmentMap<K, V> 
+where
+    K: PartialOrd
+{
+    fn extend<I>(&mut self, iter: I) 
+    where
+        I: IntoIterator<Item = (Segment<K>, V)>
+    {
+        for (segment, value) in iter {
+            self.insert(segment, value);
+        }
+    }
+}
+
+impl<K, V> Extend<(Range<K>, V)> for SegmentMap<K, V> 
+where
+    K: PartialOrd
+{
+    fn extend<I>(&mut self, iter: I) 
+    where
+        I: IntoIterator<Item = (Range<K>, V)>
+    {
+        for (range, value) in iter {
+            self.insert(Segment::new(range.start, range.end), value);
+        }
+    }
+}
+
+impl<K, V> SegmentMap<K, V>
+where
+    K: PartialOrd
+{
+    pub fn into_iter_from(mut self, key: &K) -> IntoIter<K, V> {
+        let mut stack = Vec::new();
+        let mut current = self.root.take();
+        while let Some(node) = current.take() {
+            // if self segment ends after key, it will be yielded, so descend left
+            if node.segment.upper() > key {
+                stack.push((node.segment, node.value, *node.right));
+                current = *node.left;
+            // otherwise, self segment and left are skipped, so descend right
+            } else {
+                current = *node.right;
+            }
+        }
+        IntoIter {
+            current: None,
+            stack,
+        }
+    }
+
+    pub fn partition<F>(self, mut f: F) -> (SegmentMap<K, V>, SegmentMap<K, V>)
+    where
+        F: FnMut(&Segment<K>, &V) -> bool
+    {
+        let (matching, rest) = self.into_iter().partition(|(segment, value)| f(segment, value));
+        (SegmentMap::from_sorted(matching), SegmentMap::from_sorted(rest))
+    }
+
+    /// Splits into `n` balanced maps of consecutive entries whose lengths differ by at most
+    /// one, with the first `len % n` maps one longer. Entries are never clipped.
+    pub fn split_into_chunks(self, n: usize) -> Vec<SegmentMap<K, V>> {
+        // if there are no chunks, there is nothing to split into
+        if n == 0 {
+            return Vec::new();
+        }
+        let len = self.len();
+        let mut entries = self.into_iter();
+        (0..n)
+            .map(|index| {
+                let chunk_len = (len / n) + if index < (len % n) { 1 } else { 0 };
+                SegmentMap::from_sorted(entries.by_ref().take(chunk_len).collect())
+            })
+            .collect()
+    }
+
+    pub fn into_sorted_vec(self) -> Vec<(Segment<K>, V)> {
+        let mut entries = Vec::with_capacity(self.len());
+        entries.extend(self);
+        entries
+    }
+}
+
+impl<K, V> IntoIterator for SegmentMap<K, V> {
+    type Item = (Segment<K>, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        IntoIter {
+            current: self.root.take(),
+            stack: Vec::new(),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub struct IntoIter<K, V> {
+    current: Option<SegmentMapNode<K, V>>,
+    stack: Vec<(Segment<K>, V, Option<SegmentMapNode<K, V>>)>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (Segment<K>, V);
+
+    fn next(&mut self) -> Option<(Segment<K>, V)> {
+        while let Some(current) = self.current.take() {
+            self.stack.push((current.segment, current.value, *current.right));
+            self.current = *current.left;
+        }
+        if let Some((segment, value, right)) = self.stack.pop() {
+            self.current = right;
+            Some((segment, value))
+        } else { None }
+    }
+}
+
+#[macro_export]
+macro_rules! segment_map {
+    ($($x:expr => $y:expr),*) => {{
+        #[allow(unused_mut)]
+        let mut temp_segment_map = $crate::SegmentMap::new();
+        $(temp_segment_map.insert($x, $y);)*
+        temp_segment_map
+    }}
+}
+
+#[cfg(feature = "testing")]
+impl<K, V> SegmentMap<K, V>
+where
+    K: fmt::Debug + PartialOrd,
+    V: fmt::Debug + PartialEq,
+{
+    pub fn assert_entries(&self, expected: &[(Segment<K>, V)]) {
+        let actual = self.iter().collect::<Vec<_>>();
+        let matches = (actual.len() == expected.len()) &&
+            actual.iter().zip(expected).all(|((segment, value), (expected_segment, expected_value))| (*segment == expected_segment) && (*value == expected_value));
+        if matches {
+            return;
+        }
+        // render each side per index, marking mismatched rows
+        let render = |entry: Option<(&Segment<K>, &V)>| entry.map_or_else(|| String::from("-"), |(segment, value)| format!("{:?} => {:?}", segment, value));
+        let rows = (0..actual.len().max(expected.len()))
+            .map(|index| {
+                let left = render(actual.get(index).copied());
+                let right = render(expected.get(index).map(|(segment, value)| (segment, value)));
+                (left, right)
+            })
+            .collect::<Vec<_>>();
+        let width = rows.iter().map(|(left, _)| left.len()).max().unwrap_or(0).max("actual".len());
+        let mut diff = format!("  {:<width$} | expected\n", "actual", width = width);
+        for (left, right) in rows {
+            let marker = if left == right { ' ' } else { '!' };
+            diff.push_str(&format!("{} {:<width$} | {}\n", marker, left, right, width = width));
+        }
+        panic!("entries differ:\n{}", diff);
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V> arbitrary::Arbitrary<'a> for SegmentMap<K, V>
+where
+    K: arbitrary::Arbitrary<'a> + Clone + PartialOrd,
+    V: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<SegmentMap<K, V>> {
+        // incomparable breakpoints (e.g. NaN) cannot be ordered, so they are discarded
+        let mut breakpoints = Vec::<K>::arbitrary(u)?;
+        breakpoints.retain(|breakpoint| breakpoint.partial_cmp(breakpoint).is_some());
+        breakpoints.sort_by(|a, b| a.partial_cmp(b).expect("breakpoints must be comparable"));
+        breakpoints.dedup_by(|a, b| a == b);
+        // each pair of consecutive breakpoints is either a gap or a segment
+        let mut entries = Vec::new();
+        for window in breakpoints.windows(2) {
+            if bool::arbitrary(u)? {
+                entries.push((Segment::new(window[0].clone(), window[1].clone()), V::arbitrary(u)?));
+            }
+        }
+        Ok(SegmentMap::from_sorted(entries))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K, V> SegmentMap<K, V>
+where
+    K: PartialOrd + serde::Serialize,
+    V: serde::Serialize,
+{
+    /// Writes one `{"segment":[lower,upper],"value":...}` object per line in sorted order,
+    /// without building the whole serialization in memory.
+    pub fn write_jsonl<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for (segment, value) in self.iter() {
+            writer.write_all(b"{\"segment\":[")?;
+            serde_json::to_writer(&mut *writer, segment.lower())?;
+            writer.write_all(b",")?;
+            serde_json::to_writer(&mut *writer, segment.upper())?;
+            writer.write_all(b"],\"value\":")?;
+            serde_json::to_writer(&mut *writer, value)?;
+            writer.write_all(b"}\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl<K, V> borsh::BorshSerialize for SegmentMap<K, V>
+where
+    K: borsh::BorshSerialize + PartialOrd,
+    V: borsh::BorshSerialize,
+{
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        // length prefix matches how borsh encodes sequences
+        let len = u32::try_from(self.len())
+            .map_err(|_| borsh::io::Error::new(borsh::io::ErrorKind::InvalidInput, "too many entries"))?;
+        len.serialize(writer)?;
+        for (segment, value) in self.iter() {
+            segment.serialize(writer)?;
+            value.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl<K, V> borsh::BorshDeserialize for SegmentMap<K, V>
+where
+    K: borsh::BorshDeserialize + PartialOrd,
+    V: borsh::BorshDeserialize,
+{
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<SegmentMap<K, V>> {
+        let len = u32::deserialize_reader(reader)?;
+        // length is untrusted, so entries are not preallocated
+        let mut entries = Vec::new();
+        for _ in 0..len {
+            let segment = Segment::deserialize_reader(reader)?;
+            let value = V::deserialize_reader(reader)?;
+            entries.push((segment, value));
+        }
+        SegmentMap::from_sorted_checked(entries)
+            .map_err(|error| borsh::io::Error::new(borsh::io::ErrorKind::InvalidData, error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        segment_map_node::SegmentMapNode,
+        BuildError,
+        Change,
+        Segment,
+        SegmentMap,
+    };
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_insert_multiple_empty() {
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(0, 1), 0);
+        segment_map.insert(Segment::new(1, 1), 1);
+        assert!(std::panic::catch_unwind(move || segment_map.insert(Segment::new(1, 1), 2)).is_err());
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 6) => 1,
+            Segment::new(12, 18) => 2
+        };
+        assert!(segment_map.overlaps(&Segment::new(3, 9)));
+        assert!(segment_map.overlaps(&Segment::new(8, 14)));
+        assert!(!segment_map.overlaps(&Segment::new(6, 12)));
+        assert!(!segment_map.overlaps(&Segment::new(18, 24)));
+        assert!(!segment_map.overlaps(&Segment::new(7, 11)));
+        assert!(!segment_map.overlaps(&Segment::new(3, 3)));
+        assert!(!SegmentMap::<i32, i32>::new().overlaps(&Segment::new(0, 18)));
+    }
+
+    #[test]
+    fn test_iter_from() {
+        let segment_map = segment_map! {
+            Segment::new(6, 12) => 1,
+            Segment::new(0, 6) => 0,
+            Segment::new(12, 18) => 2
+        };
+        assert_eq!(vec![(&Segment::new(6, 12), &1), (&Segment::new(12, 18), &2)], segment_map.iter_from(&8).collect::<Vec<_>>());
+        assert_eq!(vec![(&Segment::new(12, 18), &2)], segment_map.iter_from(&12).collect::<Vec<_>>());
+        assert_eq!(3, segment_map.iter_from(&-1).count());
+        assert_eq!(0, segment_map.iter_from(&18).count());
+        assert_eq!(vec![(Segment::new(6, 12), 1), (Segment::new(12, 18), 2)], segment_map.into_iter_from(&6).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_btreemap() {
+        let segment_map = segment_map! {
+            Segment::new(6, 12) => 1,
+            Segment::new(0, 6) => 0,
+            Segment::new(12, 18) => 2
+        };
+        let btreemap = segment_map.to_btreemap();
+        assert_eq!(vec![(0, (6, 0)), (6, (12, 1)), (12, (18, 2))], btreemap.clone().into_iter().collect::<Vec<_>>());
+        assert_eq!(segment_map.iter().collect::<Vec<_>>(), SegmentMap::from_btreemap(btreemap).iter().collect::<Vec<_>>());
+        let overlapping = vec![(0, (7, 0)), (6, (12, 1))].into_iter().collect();
+        assert!(std::panic::catch_unwind(|| SegmentMap::from_btreemap(overlapping)).is_err());
+    }
+
+    #[test]
+    fn test_to_btreemap_empty_collision() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 6) => 1,
+            Segment::new(6, 12) => 2,
+            Segment::new(14, 14) => 3
+        };
+        // the empty segment at 6 shares its lower with the following segment, so it is dropped
+        assert_eq!(vec![(0, (6, 0)), (6, (12, 2)), (14, (14, 3))], segment_map.to_btreemap().into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_dedup_values() {
+        let mut segment_map = segment_map! {
+            Segment::new(6, 12) => 1,
+            Segment::new(0, 6) => 0,
+            Segment::new(12, 18) => 2,
+            Segment::new(20, 24) => 2
+        };
+        for value in segment_map.values_mut() {
+            if *value == 1 {
+                *value = 2;
+            }
+        }
+        segment_map.dedup_values();
+        assert_eq!(vec![
+            (Segment::new(0, 6), 0),
+            (Segment::new(6, 18), 2),
+            (Segment::new(20, 24), 2)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_entries_by_value() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 2,
+            Segment::new(6, 12) => 0,
+            Segment::new(12, 18) => 1
+        };
+        assert_eq!(vec![
+            (&Segment::new(6, 12), &0),
+            (&Segment::new(12, 18), &1),
+            (&Segment::new(0, 6), &2)
+        ], segment_map.entries_by_value());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(12, 18) => 2,
+            Segment::new(6, 12) => 1
+        };
+        let mut cursor = segment_map.cursor();
+        assert_eq!(Some((&Segment::new(0, 6), &0)), cursor.current());
+        assert_eq!(None, cursor.peek_prev());
+        assert_eq!(Some((&Segment::new(6, 12), &1)), cursor.peek_next());
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(Some((&Segment::new(12, 18), &2)), cursor.current());
+        assert_eq!(None, cursor.peek_next());
+        cursor.move_prev();
+        assert_eq!(Some((&Segment::new(6, 12), &1)), cursor.current());
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(None, cursor.current());
+        cursor.move_prev();
+        assert_eq!(Some((&Segment::new(12, 18), &2)), cursor.current());
+    }
+
+    #[test]
+    fn test_try_from_iter() {
+        assert!(SegmentMap::try_from_iter(vec![
+            (Segment::new(0, 6), 0),
+            (Segment::new(6, 12), 1)
+        ]).is_ok());
+        let errors = SegmentMap::try_from_iter(vec![
+            (Segment::new(0, 6), 0),
+            (Segment::new(3, 9), 1),
+            (Segment::new(12, 18), 2),
+            (Segment::new(12, 18), 3),
+            (Segment::new(18, 24), 4)
+        ]).unwrap_err();
+        assert_eq!(vec![
+            (Segment::new(3, 9), 1),
+            (Segment::new(12, 18), 3)
+        ], errors.into_iter().map(|error| error.into_inner()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_remove_narrow() {
+        let mut segment_map = segment_map! {
+            Segment::new(0u32, 2) => 0,
+            Segment::new(2, 7) => 1,
+            Segment::new(10, 11) => 2
+        };
+        segment_map.remove_narrow(3);
+        assert_eq!(vec![(Segment::new(2, 7), 1)], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_longest_run() {
+        let segment_map = segment_map! {
+            Segment::new(0, 3) => 'a',
+            Segment::new(5, 8) => 'b',
+            Segment::new(8, 12) => 'c',
+            Segment::new(14, 20) => 'd'
+        };
+        assert_eq!(Some(Segment::new(5, 12)), segment_map.longest_run(Segment::new(1, 16)));
+        assert_eq!(Some(Segment::new(14, 20)), segment_map.longest_run(Segment::new(9, 25)));
+        assert_eq!(Some(Segment::new(1, 3)), segment_map.longest_run(Segment::new(1, 4)));
+        assert_eq!(None, segment_map.longest_run(Segment::new(12, 14)));
+    }
+
+    #[test]
+    fn test_coalesce_within() {
+        let mut segment_map = segment_map! {
+            Segment::new(0u32, 5) => 'a',
+            Segment::new(6, 10) => 'a',
+            Segment::new(12, 14) => 'a',
+            Segment::new(14, 16) => 'b',
+            Segment::new(17, 20) => 'a'
+        };
+        segment_map.coalesce_within(1);
+        assert_eq!(vec![
+            (Segment::new(0, 10), 'a'),
+            (Segment::new(12, 14), 'a'),
+            (Segment::new(14, 16), 'b'),
+            (Segment::new(17, 20), 'a'),
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_point_count() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 12) => 1,
+            Segment::new(14, 20) => 2,
+            Segment::new(22, 22) => 3
+        };
+        assert_eq!(18, segment_map.point_count());
+        assert_eq!(0, SegmentMap::<u8, i32>::new().point_count());
+    }
+
+    #[test]
+    fn test_dominant_value() {
+        let segment_map = segment_map! {
+            Segment::new(0u32, 2) => 'b',
+            Segment::new(2, 12) => 'a',
+            Segment::new(12, 14) => 'b',
+            Segment::new(14, 16) => 'b',
+            Segment::new(20, 30) => 'a'
+        };
+        assert_eq!(Some((&'a', 20)), segment_map.dominant_value());
+        let segment_map = segment_map! {
+            Segment::new(0u32, 2) => 'b',
+            Segment::new(2, 4) => 'a'
+        };
+        assert_eq!(Some((&'b', 2)), segment_map.dominant_value());
+        assert_eq!(None, SegmentMap::<u32, char>::new().dominant_value());
+    }
+
+    #[test]
+    fn test_inverted() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 12) => 1
+        };
+        let expected = segment_map.clone();
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| segment_map.remove(&Segment::new(9, 3))));
+        assert_eq!(expected, segment_map);
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| segment_map.update(&Segment::new(9, 3), |_| Some(2))));
+        assert_eq!(expected, segment_map);
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| segment_map.update_entry(&Segment::new(9, 3), |_, _| None)));
+        assert_eq!(expected, segment_map);
+    }
+
+    #[test]
+    fn test_coverage_ratio() {
+        let segment_map = segment_map! {
+            Segment::new(0u32, 4) => 0,
+            Segment::new(6, 12) => 1,
+            Segment::new(14, 16) => 2
+        };
+        assert_eq!(5, segment_map.covered_length_in(&Segment::new(2, 9)));
+        assert_eq!(0.5, segment_map.coverage_ratio(&Segment::new(0, 24)));
+        assert_eq!(0.0, segment_map.coverage_ratio(&Segment::new(5, 5)));
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 2) => 'a',
+            Segment::new(2, 4) => 'a',
+            Segment::new(4, 6) => 'a',
+            Segment::new(6, 8) => 'b',
+            Segment::new(9, 10) => 'b'
+        };
+        assert_eq!(2, segment_map.coalesce());
+        assert_eq!(vec![
+            (Segment::new(0, 6), 'a'),
+            (Segment::new(6, 8), 'b'),
+            (Segment::new(9, 10), 'b')
+        ], segment_map.clone().into_iter().collect::<Vec<_>>());
+        assert_eq!(0, segment_map.coalesce());
+    }
+
+    #[test]
+    fn test_fill_gaps_with() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 2) => 'a',
+            Segment::new(4, 6) => 'b',
+            Segment::new(8, 12) => 'c'
+        };
+        let mut gaps = Vec::new();
+        segment_map.fill_gaps_with(Segment::new(1, 10), |gap| {
+            gaps.push(*gap);
+            '-'
+        });
+        assert_eq!(vec![Segment::new(2, 4), Segment::new(6, 8)], gaps);
+        assert_eq!(vec![
+            (Segment::new(0, 2), 'a'),
+            (Segment::new(2, 4), '-'),
+            (Segment::new(4, 6), 'b'),
+            (Segment::new(6, 8), '-'),
+            (Segment::new(8, 12), 'c')
+        ], segment_map.clone().into_iter().collect::<Vec<_>>());
+        segment_map.fill_gaps_with(Segment::new(10, 14), |_| '+');
+        assert_eq!(Some(&'+'), segment_map.get(&13));
+        assert_eq!(6, segment_map.len());
+
+        // empty segments within a gap are removed, those at its bounds are kept
+        let mut segment_map = segment_map! {
+            Segment::new(0, 2) => 'a',
+            Segment::new(2, 2) => 'e',
+            Segment::new(3, 3) => 'f',
+            Segment::new(4, 6) => 'b',
+            Segment::new(6, 6) => 'g'
+        };
+        segment_map.fill_gaps_with(Segment::new(0, 6), |_| '-');
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(0, 2), 'a'),
+            (Segment::new(2, 2), 'e'),
+            (Segment::new(2, 4), '-'),
+            (Segment::new(4, 6), 'b'),
+            (Segment::new(6, 6), 'g')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_value_count() {
+        let segment_map = segment_map! {
+            Segment::new(0, 2) => 'a',
+            Segment::new(2, 4) => 'b',
+            Segment::new(5, 6) => 'a'
+        };
+        assert_eq!(2, segment_map.value_count());
+        assert_eq!(0, SegmentMap::<i32, char>::new().value_count());
+    }
+
+    #[test]
+    fn test_merge_adjacent_with() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 1,
+            Segment::new(6, 12) => 2,
+            Segment::new(12, 18) => 10,
+            Segment::new(20, 24) => 3
+        };
+        segment_map.merge_adjacent_with(|a, b| if a + b < 10 { Some(a + b) } else { None });
+        assert_eq!(vec![
+            (Segment::new(0, 12), 3),
+            (Segment::new(12, 18), 10),
+            (Segment::new(20, 24), 3)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_uniform_value() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 12) => 0,
+            Segment::new(12, 18) => 1,
+            Segment::new(20, 24) => 1
+        };
+        assert_eq!(Some(&0), segment_map.uniform_value(&Segment::new(3, 9)));
+        assert_eq!(Some(&0), segment_map.uniform_value(&Segment::new(0, 12)));
+        assert_eq!(None, segment_map.uniform_value(&Segment::new(3, 15)));
+        assert_eq!(None, segment_map.uniform_value(&Segment::new(15, 22)));
+        assert_eq!(None, segment_map.uniform_value(&Segment::new(20, 26)));
+        assert_eq!(None, segment_map.uniform_value(&Segment::new(3, 3)));
+    }
+
+    #[test]
+    fn test_get_degenerate() {
+        let mut root = None;
+        for key in (0..1_000_000).rev() {
+            root = Some(SegmentMapNode::new(Segment::new(key, key + 1), key, None, root));
+        }
+        let segment_map = SegmentMap { root };
+        assert_eq!(Some(&999_999), segment_map.get(&999_999));
+        assert_eq!(None, segment_map.get(&1_000_000));
+    }
+
+    #[test]
+    fn test_from_step_fn() {
+        let segment_map = SegmentMap::from_step_fn(Segment::new(0, 6), |key| key % 2);
+        assert_eq!(vec![
+            (Segment::new(0, 1), 0),
+            (Segment::new(1, 2), 1),
+            (Segment::new(2, 3), 0),
+            (Segment::new(3, 4), 1),
+            (Segment::new(4, 5), 0),
+            (Segment::new(5, 6), 1)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+        let segment_map = SegmentMap::from_step_fn(Segment::new(0, 6), |key| key / 4);
+        assert_eq!(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 6), 1)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_difference() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 12) => 1,
+            Segment::new(12, 18) => 2
+        };
+        let other = segment_map! {
+            Segment::new(3, 9) => 3,
+            Segment::new(10, 10) => 4,
+            Segment::new(12, 15) => 5,
+            Segment::new(17, 20) => 6
+        };
+        assert_eq!(vec![
+            (Segment::new(0, 3), 0),
+            (Segment::new(9, 12), 1),
+            (Segment::new(15, 17), 2)
+        ], segment_map.difference(&other).into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![
+            (Segment::new(18, 20), 6)
+        ], other.difference(&segment_map).into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_intersection_with() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 1,
+            Segment::new(6, 12) => 2
+        };
+        let other = segment_map! {
+            Segment::new(3, 9) => 10,
+            Segment::new(14, 18) => 20
+        };
+        assert_eq!(vec![
+            (Segment::new(3, 6), 11),
+            (Segment::new(6, 9), 12)
+        ], segment_map.intersection_with(&other, |a, b| a + b).into_iter().collect::<Vec<_>>());
+        let other = segment_map! {
+            Segment::new(8, 10) => 10
+        };
+        assert_eq!(vec![
+            (Segment::new(8, 10), 20)
+        ], segment_map.intersection_with(&other, |a, b| a * b).into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_scan_values() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 1,
+            Segment::new(6, 12) => 2,
+            Segment::new(14, 18) => 3
+        };
+        assert_eq!(vec![
+            (Segment::new(0, 6), 1),
+            (Segment::new(6, 12), 3),
+            (Segment::new(14, 18), 6)
+        ], segment_map.scan_values(0, |sum, value| sum + value).into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_get_disjoint_mut() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 12) => 1,
+            Segment::new(12, 18) => 2
+        };
+        if let Some([a, b]) = segment_map.get_disjoint_mut([&14, &3]) {
+            std::mem::swap(a, b);
+        }
+        assert_eq!(vec![&2, &1, &0], segment_map.values().collect::<Vec<_>>());
+        assert!(segment_map.get_disjoint_mut([&6, &11]).is_none());
+        assert!(segment_map.get_disjoint_mut([&6, &18]).is_none());
+    }
+
+    #[test]
+    fn test_non_clone_value() {
+        #[derive(Debug, PartialEq)]
+        struct Value(i32);
+
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(0, 6), Value(0));
+        segment_map.extend(vec![(Segment::new(6, 12), Value(1))]);
+        assert_eq!(Some(&Value(1)), segment_map.get(&8));
+        assert!(segment_map.try_insert(Segment::new(3, 9), Value(2)).is_err());
+    }
+
+    #[test]
+    fn test_try_update() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(8, 12) => 1
+        };
+        assert_eq!(Ok(()), segment_map.try_update(&Segment::new(4, 10), |value| Ok::<_, ()>(value.map(|value| value + 10))));
+        assert_eq!(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 6), 10),
+            (Segment::new(8, 10), 11),
+            (Segment::new(10, 12), 1)
+        ], segment_map.iter_cloned().collect::<Vec<_>>());
+        let before = segment_map.clone();
+        assert_eq!(Err("negative"), segment_map.try_update(&Segment::new(0, 12), |value| match value {
+            Some(11) => Err("negative"),
+            value => Ok(value.map(|value| value - 1)),
+        }));
+        assert_eq!(before, segment_map);
+    }
+
+    #[test]
+    fn test_try_update_restores_touched() {
+        let mut segment_map = segment_map! {
+            Segment::new(-5, -2) => 9,
+            Segment::new(0, 0) => 7,
+            Segment::new(0, 6) => 0,
+            Segment::new(7, 7) => 8,
+            Segment::new(8, 12) => 1,
+            Segment::new(12, 12) => 6,
+            Segment::new(15, 20) => 2
+        };
+        let before = segment_map.clone();
+        assert_eq!(Err(()), segment_map.try_update(&Segment::new(3, 12), |value| match value {
+            Some(1) => Err(()),
+            value => Ok(value.map(|value| value + 10).or(Some(5))),
+        }));
+        assert!(segment_map.validate());
+        assert_eq!(before, segment_map);
+        assert_eq!(Err(()), segment_map.try_update(&Segment::new(0, 0), |_| Err(())));
+        assert_eq!(before, segment_map);
+    }
+
+    #[test]
+    fn test_remove_if_present() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(8, 12) => 1
+        };
+        assert!(!segment_map.remove_if_present(&Segment::new(6, 8)));
+        assert!(segment_map.remove_if_present(&Segment::new(5, 9)));
+        assert!(!segment_map.remove_if_present(&Segment::new(5, 9)));
+        assert_eq!(vec![
+            (Segment::new(0, 5), 0),
+            (Segment::new(9, 12), 1)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments at the bounds are found, but only removed when equal
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 6) => 1,
+            Segment::new(10, 10) => 2,
+            Segment::new(12, 20) => 3
+        };
+        assert!(!segment_map.remove_if_present(&Segment::new(6, 10)));
+        assert!(segment_map.remove_if_present(&Segment::new(10, 10)));
+        assert!(segment_map.remove_if_present(&Segment::new(15, 15)));
+        assert!(!segment_map.remove_if_present(&Segment::new(20, 20)));
+        assert!(segment_map.remove_if_present(&Segment::new(5, 7)));
+        assert_eq!(vec![
+            (Segment::new(0, 5), 0),
+            (Segment::new(12, 15), 3),
+            (Segment::new(15, 20), 3)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(8, 12) => 1,
+            Segment::new(14, 18) => 2
+        };
+        segment_map.paint(Segment::new(3, 16), 3);
+        assert_eq!(vec![
+            (Segment::new(0, 3), 0),
+            (Segment::new(3, 16), 3),
+            (Segment::new(16, 18), 2)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_insert_resolving() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 1,
+            Segment::new(8, 12) => 2,
+            Segment::new(14, 18) => 3
+        };
+        segment_map.insert_resolving(Segment::new(4, 10), 10, |existing, value| existing + value);
+        assert_eq!(vec![
+            (Segment::new(0, 4), 1),
+            (Segment::new(4, 6), 11),
+            (Segment::new(6, 8), 10),
+            (Segment::new(8, 10), 12),
+            (Segment::new(10, 12), 2),
+            (Segment::new(14, 18), 3)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments strictly within segment are absorbed
+        let mut segment_map = segment_map! {
+            Segment::new(0, 0) => 1,
+            Segment::new(5, 5) => 2,
+            Segment::new(10, 10) => 3
+        };
+        segment_map.insert_resolving(Segment::new(0, 10), 10, |existing, value| existing + value);
+        assert_eq!(vec![
+            (Segment::new(0, 0), 1),
+            (Segment::new(0, 10), 10),
+            (Segment::new(10, 10), 3)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // an empty segment splits the segment it falls within
+        let mut segment_map = segment_map! {
+            Segment::new(0, 10) => 1
+        };
+        segment_map.insert_resolving(Segment::new(5, 5), 10, |existing, value| existing + value);
+        segment_map.insert_resolving(Segment::new(5, 5), 10, |existing, value| existing + value);
+        segment_map.insert_resolving(Segment::new(10, 10), 10, |existing, value| existing + value);
+        assert_eq!(vec![
+            (Segment::new(0, 5), 1),
+            (Segment::new(5, 5), 21),
+            (Segment::new(5, 10), 1),
+            (Segment::new(10, 10), 10)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_apply_sorted() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 12) => 1,
+            Segment::new(14, 18) => 2,
+            Segment::new(20, 24) => 3
+        };
+        let updates = vec![
+            (Segment::new(2, 4), 4),
+            (Segment::new(10, 16), 5),
+            (Segment::new(18, 20), 6)
+        ];
+        let mut expected = segment_map.clone();
+        for (region, value) in updates.clone() {
+            expected.update(&region, |_| Some(value));
+        }
+        let mut segment_map = segment_map;
+        segment_map.apply_sorted(updates);
+        assert_eq!(vec![
+            (Segment::new(0, 2), 0),
+            (Segment::new(2, 4), 4),
+            (Segment::new(4, 6), 0),
+            (Segment::new(6, 10), 1),
+            (Segment::new(10, 16), 5),
+            (Segment::new(16, 18), 2),
+            (Segment::new(18, 20), 6),
+            (Segment::new(20, 24), 3)
+        ], segment_map.iter().map(|(segment, value)| (*segment, *value)).collect::<Vec<_>>());
+        expected.dedup_values();
+        segment_map.dedup_values();
+        assert_eq!(expected.into_iter().collect::<Vec<_>>(), segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments are kept unless a region equals or strictly encloses them
+        let segment_map = segment_map! {
+            Segment::new(0, 10) => 1,
+            Segment::new(10, 10) => 7,
+            Segment::new(20, 20) => 8,
+            Segment::new(30, 30) => 9,
+            Segment::new(50, 50) => 10
+        };
+        let updates = vec![
+            (Segment::new(2, 4), 5),
+            (Segment::new(15, 25), 6),
+            (Segment::new(30, 30), 7)
+        ];
+        let mut expected = segment_map.clone();
+        for (region, value) in updates.clone() {
+            expected.update(&region, |_| Some(value));
+        }
+        let mut segment_map = segment_map;
+        segment_map.apply_sorted(updates);
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(0, 2), 1),
+            (Segment::new(2, 4), 5),
+            (Segment::new(4, 10), 1),
+            (Segment::new(10, 10), 7),
+            (Segment::new(15, 25), 6),
+            (Segment::new(30, 30), 7),
+            (Segment::new(50, 50), 10)
+        ], segment_map.iter().map(|(segment, value)| (*segment, *value)).collect::<Vec<_>>());
+        assert_eq!(expected.into_iter().collect::<Vec<_>>(), segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_min_max() {
+        let segment_map = segment_map! {
+            Segment::new(6, 12) => 1,
+            Segment::new(12, 18) => 2,
+            Segment::new(0, 6) => 0
+        };
+        assert_eq!(Some(&Segment::new(0, 6)), segment_map.min_segment());
+        assert_eq!(Some(&0), segment_map.min_value());
+        assert_eq!(Some(&Segment::new(12, 18)), segment_map.max_segment());
+        assert_eq!(Some(&2), segment_map.max_value());
+        assert_eq!(None, SegmentMap::<i32, i32>::new().min_segment());
+    }
+
+    #[test]
+    fn test_insert_or_merge() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 5) => 'a',
+            Segment::new(12, 18) => 'a'
+        };
+        segment_map.insert_or_merge(Segment::new(5, 10), 'a');
+        assert_eq!(vec![
+            (Segment::new(0, 10), 'a'),
+            (Segment::new(12, 18), 'a')
+        ], segment_map.iter().map(|(segment, value)| (*segment, *value)).collect::<Vec<_>>());
+        segment_map.insert_or_merge(Segment::new(10, 12), 'b');
+        segment_map.insert_or_merge(Segment::new(18, 20), 'a');
+        assert_eq!(vec![
+            (Segment::new(0, 10), 'a'),
+            (Segment::new(10, 12), 'b'),
+            (Segment::new(12, 20), 'a')
+        ], segment_map.iter().map(|(segment, value)| (*segment, *value)).collect::<Vec<_>>());
+        assert!(std::panic::catch_unwind(move || segment_map.insert_or_merge(Segment::new(8, 11), 'a')).is_err());
+    }
+
+    #[test]
+    fn test_insert_or_merge_empty_boundary() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 5) => 'a',
+            Segment::new(5, 5) => 'e',
+            Segment::new(10, 10) => 'e',
+            Segment::new(10, 15) => 'a',
+            Segment::new(20, 20) => 'e'
+        };
+        // empty segments at both boundaries block merging rather than losing neighbors
+        segment_map.insert_or_merge(Segment::new(5, 10), 'a');
+        assert!(segment_map.validate());
+        // an empty segment within the new segment is rejected before neighbors are removed
+        let mut blocked = segment_map.clone();
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| blocked.insert_or_merge(Segment::new(15, 25), 'a'))).is_err());
+        assert_eq!(segment_map, blocked);
+        assert_eq!(vec![
+            (Segment::new(0, 5), 'a'),
+            (Segment::new(5, 5), 'e'),
+            (Segment::new(5, 10), 'a'),
+            (Segment::new(10, 10), 'e'),
+            (Segment::new(10, 15), 'a'),
+            (Segment::new(20, 20), 'e')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_iter_cloned() {
+        let segment_map = segment_map! {
+            Segment::new(6, 12) => String::from("b"),
+            Segment::new(0, 6) => String::from("a")
+        };
+        assert_eq!(
+            segment_map.iter().map(|(segment, value)| (*segment, value.clone())).collect::<Vec<_>>(),
+            segment_map.iter_cloned().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_debug() {
+        let segment_map = segment_map! {
+            Segment::new(6, 12) => 1,
+            Segment::new(0, 6) => 0
+        };
+        assert_eq!(
+            "{Segment { lower: 0, upper: 6 }: 0, Segment { lower: 6, upper: 12 }: 1}",
+            format!("{:?}", segment_map)
+        );
+    }
+
+    #[test]
+    fn test_invariants_after_ops() {
+        let seed = std::env::var("SEGMENT_MAP_SEED").ok().and_then(|seed| seed.parse().ok()).filter(|&seed| seed != 0).unwrap_or(0x2545_f491_4f6c_dd1d_u64);
+        let mut state = seed;
+        let mut random = move |bound: i32| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as i32
+        };
+        let mut segment_map = SegmentMap::new();
+        let mut expected: Vec<Option<i32>> = vec![None; 64];
+        for operation in 0..5000 {
+            let a = random(64);
+            let b = random(64);
+            let segment = Segment::new(a.min(b), a.max(b));
+            let value = random(4);
+            let points = a.min(b) as usize..a.max(b) as usize;
+            match random(4) {
+                0 => if segment_map.try_insert(segment, value).is_ok() {
+                    for point in points {
+                        expected[point] = Some(value);
+                    }
+                },
+                1 => {
+                    segment_map.remove(&segment);
+                    for point in points {
+                        expected[point] = None;
+                    }
+                },
+                2 => {
+                    segment_map.update(&segment, |_| Some(value));
+                    for point in points {
+                        expected[point] = Some(value);
+                    }
+                },
+                _ => {
+                    segment_map.update(&segment, |old| old.map(|old| old + 1));
+                    for point in points {
+                        expected[point] = expected[point].map(|old| old + 1);
+                    }
+                },
+            }
+            assert!(segment_map.validate(), "seed: {}, operation: {}", seed, operation);
+            for (point, expected) in expected.iter().enumerate() {
+                assert_eq!(expected.as_ref(), segment_map.get(&(point as i32)), "seed: {}, operation: {}, point: {}", seed, operation, point);
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_strict() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(12, 18) => 1
+        };
+        assert!(segment_map.insert_strict(Segment::new(6, 9), 0).is_err());
+        assert!(segment_map.insert_strict(Segment::new(9, 12), 1).is_err());
+        assert!(segment_map.insert_strict(Segment::new(6, 12), 2).is_ok());
+        assert_eq!(3, segment_map.iter().count());
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let segment_map = segment_map! {
+            Segment::new(6, 12) => 1,
+            Segment::new(0, 6) => 0
+        };
+        assert_eq!(vec![&0, &6, &12], segment_map.boundaries().collect::<Vec<_>>());
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(6, 6) => 1,
+            Segment::new(8, 12) => 2
+        };
+        assert_eq!(vec![&0, &6, &8, &12], segment_map.boundaries().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_zip_with() {
+        let segment_map = segment_map! {
+            Segment::new(0, 12) => 'a',
+            Segment::new(14, 18) => 'b'
+        };
+        let other = segment_map! {
+            Segment::new(3, 6) => 1,
+            Segment::new(6, 9) => 2,
+            Segment::new(16, 20) => 3
+        };
+        assert_eq!(vec![
+            (Segment::new(0, 3), (Some(&'a'), None)),
+            (Segment::new(3, 6), (Some(&'a'), Some(&1))),
+            (Segment::new(6, 9), (Some(&'a'), Some(&2))),
+            (Segment::new(9, 12), (Some(&'a'), None)),
+            (Segment::new(14, 16), (Some(&'b'), None)),
+            (Segment::new(16, 18), (Some(&'b'), Some(&3))),
+            (Segment::new(18, 20), (None, Some(&3)))
+        ], segment_map.zip_with(&other, |value, other| (value, other)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_drain_range() {
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(0, 10), 0);
+        segment_map.insert(Segment::new(12, 14), 1);
+        segment_map.insert(Segment::new(15, 30), 2);
+        segment_map.insert(Segment::new(40, 50), 3);
+        let drained = segment_map.drain_range(Segment::new(5, 20)).collect::<Vec<_>>();
+        assert_eq!(vec![
+            (Segment::new(5, 10), 0),
+            (Segment::new(12, 14), 1),
+            (Segment::new(15, 20), 2),
+        ], drained);
+        assert_eq!(vec![
+            (Segment::new(0, 5), 0),
+            (Segment::new(20, 30), 2),
+            (Segment::new(40, 50), 3),
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(0, 100), 0);
+        let drained = segment_map.drain_range(Segment::new(40, 60)).collect::<Vec<_>>();
+        assert_eq!(vec![(Segment::new(40, 60), 0)], drained);
+        assert_eq!(vec![
+            (Segment::new(0, 40), 0),
+            (Segment::new(60, 100), 0),
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments strictly within window are drained, those at its bounds remain
+        let mut segment_map = segment_map! {
+            Segment::new(0, 5) => 0,
+            Segment::new(5, 5) => 1,
+            Segment::new(10, 10) => 2,
+            Segment::new(20, 20) => 3
+        };
+        let drained = segment_map.drain_range(Segment::new(3, 20)).collect::<Vec<_>>();
+        assert_eq!(vec![
+            (Segment::new(3, 5), 0),
+            (Segment::new(5, 5), 1),
+            (Segment::new(10, 10), 2),
+        ], drained);
+        assert_eq!(vec![
+            (Segment::new(0, 3), 0),
+            (Segment::new(20, 20), 3),
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut segment_map = segment_map! {
+            Segment::new(-2, 2) => 'a',
+            Segment::new(4, 6) => 'b',
+            Segment::new(12, 14) => 'c'
+        };
+        segment_map.invert(Segment::new(0, 10), 'x');
+        assert_eq!(vec![
+            (Segment::new(-2, 0), 'a'),
+            (Segment::new(2, 4), 'x'),
+            (Segment::new(6, 10), 'x'),
+            (Segment::new(12, 14), 'c')
+        ], segment_map.clone().into_iter().collect::<Vec<_>>());
+        segment_map.invert(Segment::new(0, 10), 'y');
+        assert_eq!(vec![
+            (Segment::new(-2, 0), 'a'),
+            (Segment::new(0, 2), 'y'),
+            (Segment::new(4, 6), 'y'),
+            (Segment::new(12, 14), 'c')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments within a gap are dropped, those at its bounds are kept
+        let mut segment_map = segment_map! {
+            Segment::new(0, 2) => 'a',
+            Segment::new(2, 2) => 'e',
+            Segment::new(3, 3) => 'f',
+            Segment::new(6, 6) => 'g'
+        };
+        segment_map.invert(Segment::new(0, 6), 'x');
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(2, 2), 'e'),
+            (Segment::new(2, 6), 'x'),
+            (Segment::new(6, 6), 'g')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rotate_within() {
+        let mut segment_map = segment_map! {
+            Segment::new(-5, 0) => 'x',
+            Segment::new(2, 4) => 'a',
+            Segment::new(7, 9) => 'b',
+            Segment::new(9, 12) => 'c'
+        };
+        segment_map.rotate_within(Segment::new(0, 10), 2);
+        assert_eq!(vec![
+            (Segment::new(-5, 0), 'x'),
+            (Segment::new(0, 1), 'b'),
+            (Segment::new(1, 2), 'c'),
+            (Segment::new(4, 6), 'a'),
+            (Segment::new(9, 10), 'b'),
+            (Segment::new(10, 12), 'c')
+        ], segment_map.clone().into_iter().collect::<Vec<_>>());
+        segment_map.rotate_within(Segment::new(0, 10), -12);
+        assert_eq!(vec![
+            (Segment::new(-5, 0), 'x'),
+            (Segment::new(2, 4), 'a'),
+            (Segment::new(7, 8), 'b'),
+            (Segment::new(8, 9), 'b'),
+            (Segment::new(9, 10), 'c'),
+            (Segment::new(10, 12), 'c')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments move with the coverage instead of ending up within it
+        let mut segment_map = segment_map! {
+            Segment::new(0, 5) => 'a',
+            Segment::new(5, 5) => 'e',
+            Segment::new(10, 10) => 'f'
+        };
+        segment_map.rotate_within(Segment::new(0, 10), 3);
+        assert_eq!(vec![
+            (Segment::new(3, 8), 'a'),
+            (Segment::new(8, 8), 'e'),
+            (Segment::new(10, 10), 'f')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rotate_within_unsigned() {
+        // shifting near the top of the key range doesn't overflow
+        let mut segment_map = segment_map! {
+            Segment::new(240u8, 250u8) => 'a'
+        };
+        segment_map.rotate_within(Segment::new(0, 250), 100);
+        assert_eq!(vec![
+            (Segment::new(90, 100), 'a')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        let mut segment_map = segment_map! {
+            Segment::new(0u8, 0u8) => 'z',
+            Segment::new(0u8, 10u8) => 'b',
+            Segment::new(30u8, 60u8) => 'c',
+            Segment::new(100u8, 100u8) => 'e',
+            Segment::new(190u8, 200u8) => 'a',
+            Segment::new(200u8, 200u8) => 'w'
+        };
+        segment_map.rotate_within(Segment::new(0, 200), 150);
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(0, 10), 'c'),
+            (Segment::new(50, 50), 'e'),
+            (Segment::new(140, 150), 'a'),
+            (Segment::new(150, 150), 'z'),
+            (Segment::new(150, 160), 'b'),
+            (Segment::new(180, 200), 'c'),
+            (Segment::new(200, 200), 'w')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_resample_balanced() {
+        let segment_map = segment_map! {
+            Segment::new(0, 100_000) => 0
+        };
+        let targets = (0..10_000).map(|i| Segment::new(i * 10, i * 10 + 10));
+        let resampled = segment_map.resample(targets, |target, source| source.get(target.lower()).copied());
+        assert!(resampled.validate());
+        assert_eq!(10_000, resampled.len());
+        assert!(resampled.depth_histogram().len() <= 14);
+        // unsorted targets are still accepted
+        let resampled = segment_map.resample(vec![Segment::new(5, 6), Segment::new(1, 2)], |_, _| Some(1));
+        assert_eq!(vec![(Segment::new(1, 2), 1), (Segment::new(5, 6), 1)], resampled.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_retain_keys_in() {
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(0, 10), 0);
+        segment_map.insert(Segment::new(15, 30), 1);
+        segment_map.insert(Segment::new(40, 50), 2);
+        segment_map.retain_keys_in(Segment::new(5, 20));
+        assert_eq!(vec![
+            (Segment::new(5, 10), 0),
+            (Segment::new(15, 20), 1),
+        ], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments strictly within window are retained
+        let mut segment_map = segment_map! {
+            Segment::new(0, 0) => 0,
+            Segment::new(5, 5) => 1,
+            Segment::new(10, 10) => 2
+        };
+        segment_map.retain_keys_in(Segment::new(0, 10));
+        assert_eq!(vec![
+            (Segment::new(5, 5), 1),
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_covers_same() {
+        let segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(10, 12) => 2
+        };
+        let other = segment_map! {
+            Segment::new(0, 2) => 3,
+            Segment::new(2, 8) => 3,
+            Segment::new(9, 9) => 3,
+            Segment::new(10, 12) => 4
+        };
+        assert!(segment_map.covers_same(&other));
+        assert!(other.covers_same(&segment_map));
+        let other = segment_map! {
+            Segment::new(0, 8) => 0,
+            Segment::new(10, 13) => 2
+        };
+        assert!(!segment_map.covers_same(&other));
+        let other = segment_map! {
+            Segment::new(0, 8) => 0
+        };
+        assert!(!segment_map.covers_same(&other));
+    }
+
+    #[test]
+    fn test_depth_histogram() {
+        let mut segment_map = SegmentMap::new();
+        assert_eq!(Vec::<usize>::new(), segment_map.depth_histogram());
+        for key in [5, 2, 8, 1, 3, 0, 9] {
+            segment_map.insert(Segment::new(key, key + 1), key);
+        }
+        assert_eq!(vec![1, 2, 3, 1], segment_map.depth_histogram());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{
+            Arbitrary,
+            Unstructured,
+        };
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..1000 {
+            let bytes = (0..256).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }).collect::<Vec<_>>();
+            let mut u = Unstructured::new(&bytes);
+            let segment = Segment::<i8>::arbitrary(&mut u).unwrap();
+            assert!(segment.lower() <= segment.upper());
+            let segment_map = SegmentMap::<i8, u8>::arbitrary(&mut u).unwrap();
+            assert!(segment_map.validate());
+            let segment_map = SegmentMap::<f32, u8>::arbitrary(&mut u).unwrap();
+            assert!(segment_map.validate());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_write_jsonl() {
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(5, 8), "b");
+        segment_map.insert(Segment::new(-3, 0), "a");
+        segment_map.insert(Segment::new(10, 12), "c\"");
+        let mut output = Vec::new();
+        segment_map.write_jsonl(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(3, output.lines().count());
+        assert_eq!(vec![
+            r#"{"segment":[-3,0],"value":"a"}"#,
+            r#"{"segment":[5,8],"value":"b"}"#,
+            r#"{"segment":[10,12],"value":"c\""}"#
+        ], output.lines().collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh() {
+        let segment_map = segment_map! {
+            Segment::new(-5, 0) => 1u64,
+            Segment::new(0, 0) => 2,
+            Segment::new(3, 7) => 3
+        };
+        let bytes = borsh::to_vec(&segment_map).unwrap();
+        assert_eq!(segment_map, borsh::from_slice::<SegmentMap<i32, u64>>(&bytes).unwrap());
+        assert_eq!(Segment::new(3, 7), borsh::from_slice::<Segment<i32>>(&borsh::to_vec(&Segment::new(3, 7)).unwrap()).unwrap());
+
+        // overlapping and inverted segments are rejected
+        let overlapping = borsh::to_vec(&vec![(Segment::new(0, 5), 1u64), (Segment::new(3, 7), 2)]).unwrap();
+        assert!(borsh::from_slice::<SegmentMap<i32, u64>>(&overlapping).is_err());
+        let inverted = borsh::to_vec(&(5i32, 3i32)).unwrap();
+        assert!(borsh::from_slice::<Segment<i32>>(&inverted).is_err());
+    }
+
+    #[test]
+    fn test_for_each_point() {
+        let segment_map = segment_map! {
+            Segment::new(0, 4) => 1,
+            Segment::new(6, 9) => 10,
+            Segment::new(12, 15) => 100
+        };
+        let mut sum = 0;
+        let mut points = Vec::new();
+        segment_map.for_each_point(&Segment::new(2, 8), |point, value| {
+            sum += value;
+            points.push(*point);
+        });
+        assert_eq!(22, sum);
+        assert_eq!(vec![2, 3, 6, 7], points);
+    }
+
+    #[test]
+    fn test_split_view() {
+        let segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(10, 12) => 2,
+            Segment::new(12, 14) => 3
+        };
+        let (lower, upper) = segment_map.split_view(&10);
+        assert_eq!(vec![&0, &1], lower.map(|(_, value)| value).collect::<Vec<_>>());
+        assert_eq!(vec![&2, &3], upper.map(|(_, value)| value).collect::<Vec<_>>());
+        let (lower, upper) = segment_map.split_view(&6);
+        assert_eq!(vec![&0, &1], lower.map(|(_, value)| value).collect::<Vec<_>>());
+        assert_eq!(vec![&2, &3], upper.map(|(_, value)| value).collect::<Vec<_>>());
+        let (lower, upper) = segment_map.split_view(&0);
+        assert_eq!(0, lower.count());
+        assert_eq!(4, upper.count());
+        let (mut lower, upper) = segment_map.split_view(&20);
+        assert_eq!(4, lower.by_ref().count());
+        assert_eq!(None, lower.next());
+        assert_eq!(0, upper.count());
+    }
+
+    #[test]
+    fn test_get_entries_sorted() {
+        let segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(10, 12) => 2
+        };
+        assert_eq!(vec![
+            None,
+            Some((&Segment::new(0, 4), &0)),
+            Some((&Segment::new(0, 4), &0)),
+            Some((&Segment::new(4, 8), &1)),
+            None,
+            Some((&Segment::new(10, 12), &2)),
+            None,
+            Some((&Segment::new(0, 4), &0)),
+        ], segment_map.get_entries_sorted(&[-1, 0, 3, 4, 9, 11, 12, 2]));
     }
 
-    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
-        ValuesMut { inner: self.iter_mut() }
+    #[test]
+    fn test_replace_value() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(10, 12) => 2
+        };
+        assert_eq!(Some(1), segment_map.replace_value(&5, 7));
+        assert_eq!(None, segment_map.replace_value(&9, 8));
+        assert_eq!(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 8), 7),
+            (Segment::new(10, 12), 2)
+        ], segment_map.into_iter().collect::<Vec<_>>());
     }
 
-    pub fn iter(&self) -> Iter<'_, K, V> {
-        Iter {
-            current: self.root.as_ref(),
-            stack: Vec::new(),
+    #[test]
+    fn test_split_into_chunks() {
+        let segment_map = SegmentMap::from_sorted((0..9).map(|key| (Segment::new(key, key + 1), key)).collect());
+        let chunks = segment_map.clone().split_into_chunks(3);
+        assert_eq!(3, chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.validate());
+            assert_eq!((index * 3..index * 3 + 3).collect::<Vec<_>>(), chunk.values().cloned().map(|key| key as usize).collect::<Vec<_>>());
         }
+        assert_eq!(vec![3, 2, 2, 2], segment_map.clone().split_into_chunks(4).iter().map(SegmentMap::len).collect::<Vec<_>>());
+        assert_eq!(vec![1, 1, 0], SegmentMap::from_sorted(vec![(Segment::new(0, 1), 0), (Segment::new(1, 2), 1)]).split_into_chunks(3).iter().map(SegmentMap::len).collect::<Vec<_>>());
+        assert!(segment_map.split_into_chunks(0).is_empty());
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
-        IterMut {
-            current: self.root.as_mut(),
-            stack: Vec::new(),
-        }
+    #[test]
+    fn test_partition() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 1,
+            Segment::new(6, 12) => 2,
+            Segment::new(14, 18) => 3
+        };
+        let (odd, even) = segment_map.partition(|_, value| value % 2 == 1);
+        assert_eq!(vec![
+            (Segment::new(0, 6), 1),
+            (Segment::new(14, 18), 3)
+        ], odd.into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![(Segment::new(6, 12), 2)], even.into_iter().collect::<Vec<_>>());
     }
 
-    pub fn span(&self) -> Option<Segment<&K>> {
-        self.root.as_ref().map(|root| root.span())
+    #[test]
+    fn test_into_sorted_vec() {
+        let segment_map = segment_map! {
+            Segment::new(8, 12) => 1,
+            Segment::new(0, 6) => 0,
+            Segment::new(14, 18) => 2
+        };
+        assert_eq!(3, segment_map.len());
+        let entries = segment_map.clone().into_sorted_vec();
+        assert_eq!(3, entries.capacity());
+        assert_eq!(segment_map.into_iter().collect::<Vec<_>>(), entries);
+        assert_eq!(0, SegmentMap::<i32, i32>::new().len());
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.root.is_none()
+    #[test]
+    fn test_retain_overlapping() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(10, 12) => 2,
+            Segment::new(12, 16) => 3,
+            Segment::new(18, 20) => 4,
+            Segment::new(22, 24) => 5
+        };
+        segment_map.retain_overlapping(vec![Segment::new(19, 23), Segment::new(2, 5)]);
+        assert_eq!(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 8), 1),
+            (Segment::new(18, 20), 4),
+            (Segment::new(22, 24), 5)
+        ], segment_map.into_iter().collect::<Vec<_>>());
     }
 
-    pub fn clear(&mut self) {
-        self.root = None;
+    #[test]
+    fn test_iter_flatten() {
+        let segment_map = segment_map! {
+            Segment::new(0, 4) => vec![1, 2],
+            Segment::new(4, 8) => vec![],
+            Segment::new(10, 12) => vec![3]
+        };
+        assert_eq!(vec![
+            (&Segment::new(0, 4), &1),
+            (&Segment::new(0, 4), &2),
+            (&Segment::new(10, 12), &3)
+        ], segment_map.iter_flatten().collect::<Vec<_>>());
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> {
-        self.root.as_ref().and_then(|root| root.get(key))
+    #[test]
+    fn test_clipped_iter() {
+        let segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(10, 14) => 2,
+            Segment::new(16, 20) => 3
+        };
+        assert_eq!(vec![
+            (Segment::new(2, 4), &0),
+            (Segment::new(4, 8), &1),
+            (Segment::new(10, 12), &2)
+        ], segment_map.clipped_iter(Segment::new(2, 12)).collect::<Vec<_>>());
     }
 
-    pub fn get_entry(&self, key: &K) -> Option<(&Segment<K>, &V)> {
-        self.root.as_ref().and_then(|root| root.get_entry(key))
+    #[test]
+    fn test_map_entries() {
+        let segment_map = segment_map! {
+            Segment::new(0u32, 4) => 'a',
+            Segment::new(4, 9) => 'b',
+            Segment::new(10, 10) => 'c'
+        };
+        assert_eq!(vec![
+            (Segment::new(0, 4), 4),
+            (Segment::new(4, 9), 5),
+            (Segment::new(10, 10), 0)
+        ], segment_map.map_entries(|segment, _| segment.measure()).into_iter().collect::<Vec<_>>());
     }
 
-    pub fn contains_key(&self, key: &K) -> bool {
-        self.get_entry(key).is_some()
+    #[test]
+    fn test_diff() {
+        let older = segment_map! {
+            Segment::new(0, 10) => 0,
+            Segment::new(10, 20) => 1,
+            Segment::new(30, 40) => 2
+        };
+        let newer = segment_map! {
+            Segment::new(0, 5) => 0,
+            Segment::new(5, 10) => 0,
+            Segment::new(10, 15) => 1,
+            Segment::new(15, 20) => 3,
+            Segment::new(20, 25) => 4
+        };
+        assert_eq!(vec![
+            Change::Changed(Segment::new(15, 20), 1, 3),
+            Change::Added(Segment::new(20, 25), 4),
+            Change::Removed(Segment::new(30, 40), 2),
+        ], older.diff(&newer));
+        assert!(older.diff(&older).is_empty());
     }
 
-    pub fn insert(&mut self, segment: Segment<K>, value: V) {
-        if let Some(root) = self.root.as_mut() {
-            root.insert(segment, value);
-        } else {
-            self.root = Some(SegmentMapNode::new(segment, value, None, None));
-        }
+    #[test]
+    fn test_apply_changes() {
+        let older = segment_map! {
+            Segment::new(0, 10) => 0,
+            Segment::new(10, 20) => 1,
+            Segment::new(30, 40) => 2
+        };
+        let newer = segment_map! {
+            Segment::new(0, 10) => 0,
+            Segment::new(10, 15) => 1,
+            Segment::new(15, 20) => 3,
+            Segment::new(20, 25) => 4,
+            Segment::new(32, 35) => 2
+        };
+        let mut applied = older.clone();
+        applied.apply_changes(older.diff(&newer));
+        assert!(applied.validate());
+        assert_eq!(newer, applied);
+
+        // changes only describe values, so differences in segmentation alone are not applied
+        let mut applied = newer.clone();
+        applied.apply_changes(newer.diff(&older));
+        assert!(applied.diff(&older).is_empty());
+        assert!(applied.covers_same(&older));
     }
-}
 
-impl<K, V> SegmentMap<K, V> 
-where
-    K: Clone + PartialOrd,
-    V: Clone,
-{
-    pub fn remove(&mut self, segment: &Segment<K>) {
-        if let Some(root) = self.root.take() {
-            self.root = root.remove(segment);
-        }
+    #[test]
+    fn test_diff_empty_segments() {
+        let older = segment_map! {
+            Segment::new(0, 10) => 0,
+            Segment::new(12, 12) => 1,
+            Segment::new(20, 30) => 2
+        };
+        let newer = segment_map! {
+            Segment::new(0, 10) => 0,
+            Segment::new(14, 14) => 3,
+            Segment::new(20, 30) => 4
+        };
+        // empty segments are not reported
+        assert_eq!(vec![
+            Change::Changed(Segment::new(20, 30), 2, 4),
+        ], older.diff(&newer));
+        assert!(older.diff(&segment_map! { Segment::new(0, 10) => 0, Segment::new(20, 30) => 2 }).is_empty());
+
+        // so applying the changes maps every key the same, but leaves empty segments as they were
+        let mut applied = older.clone();
+        applied.apply_changes(older.diff(&newer));
+        assert!(applied.diff(&newer).is_empty());
+        assert_eq!(vec![
+            (Segment::new(0, 10), 0),
+            (Segment::new(12, 12), 1),
+            (Segment::new(20, 30), 4)
+        ], applied.into_iter().collect::<Vec<_>>());
+
+        // except those strictly within an added segment, which painting drops
+        let older = segment_map! {
+            Segment::new(5, 5) => 1
+        };
+        let mut applied = older.clone();
+        applied.apply_changes(older.diff(&segment_map! { Segment::new(0, 10) => 2 }));
+        assert_eq!(vec![
+            (Segment::new(0, 10), 2)
+        ], applied.into_iter().collect::<Vec<_>>());
     }
 
-    pub fn update<F>(&mut self, segment: &Segment<K>, value: F) 
-    where
-        F: Fn(Option<V>) -> Option<V> + Clone
-    {
-        if let Some(root) = self.root.take() {
-            self.root = root.update(segment, value);
-        } else if let Some(value) = value(None) {
-            self.insert(segment.clone(), value);
-        }
+    #[test]
+    fn test_resample() {
+        let segment_map = segment_map! {
+            Segment::new(0, 2) => 0,
+            Segment::new(2, 5) => 1,
+            Segment::new(5, 9) => 2,
+            Segment::new(12, 14) => 3
+        };
+        let targets = (0..4).map(|i| Segment::new(i * 4, i * 4 + 4));
+        let resampled = segment_map.resample(targets, |target, source| source.get(target.lower()).copied());
+        assert_eq!(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 8), 1),
+            (Segment::new(8, 12), 2),
+            (Segment::new(12, 16), 3)
+        ], resampled.into_iter().collect::<Vec<_>>());
     }
 
-    pub fn update_entry<F>(&mut self, segment: &Segment<K>, value: F)
-    where
-        F: Fn(&Segment<K>, Option<V>) -> Option<V> + Clone
-    {
-        if let Some(root) = self.root.take() {
-            self.root = root.update_entry(segment, value);
-        } else if let Some(value) = value(segment, None) {
-            self.insert(segment.clone(), value);
-        }
+    #[test]
+    fn test_rank_select() {
+        let mut segment_map = segment_map! {
+            Segment::new(4, 8) => 1,
+            Segment::new(0, 4) => 0,
+            Segment::new(10, 12) => 2,
+            Segment::new(14, 18) => 3
+        };
+        assert_eq!(0, segment_map.rank(&-1));
+        assert_eq!(0, segment_map.rank(&2));
+        assert_eq!(1, segment_map.rank(&4));
+        assert_eq!(1, segment_map.rank(&5));
+        assert_eq!(2, segment_map.rank(&9));
+        assert_eq!(3, segment_map.rank(&13));
+        assert_eq!(4, segment_map.rank(&100));
+        assert_eq!(Some((&Segment::new(0, 4), &0)), segment_map.select(0));
+        assert_eq!(Some((&Segment::new(10, 12), &2)), segment_map.select(2));
+        assert_eq!(Some((&Segment::new(14, 18), &3)), segment_map.select(3));
+        assert_eq!(None, segment_map.select(4));
+        segment_map.remove(&Segment::new(2, 11));
+        assert!(segment_map.validate());
+        assert_eq!(3, segment_map.len());
+        assert_eq!(Some((&Segment::new(11, 12), &2)), segment_map.select(1));
+        assert_eq!(2, segment_map.rank(&12));
     }
-}
 
-pub struct Segments<'a, K, V> {
-    inner: Iter<'a, K, V>
-}
+    #[test]
+    fn test_empty_at_intersection_bound() {
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(32, 33), 0);
+        segment_map.insert(Segment::new(32, 32), 1);
+        segment_map.remove(&Segment::new(31, 35));
+        assert!(segment_map.is_empty());
 
-impl<'a, K, V> Iterator for Segments<'a, K, V> {
-    type Item = &'a Segment<K>;
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(32, 33), 0);
+        segment_map.insert(Segment::new(32, 32), 1);
+        segment_map.insert(Segment::new(33, 33), 2);
+        segment_map.update(&Segment::new(31, 35), |_| Some(3));
+        assert!(segment_map.segments().all(|segment| !segment.is_empty()));
+        assert_eq!(Some(&3), segment_map.get(&31));
+        assert_eq!(Some(&3), segment_map.get(&34));
+    }
 
-    fn next(&mut self) -> Option<&'a Segment<K>> {
-        self.inner.next().map(|(segment, _)| segment)
+    #[test]
+    fn test_remove_empty_at_intersection_bound() {
+        // empty segments at either end of an overlapped segment, strictly within the query
+        let mut segment_map = segment_map! {
+            Segment::new(10, 10) => 'l',
+            Segment::new(10, 20) => 'a',
+            Segment::new(20, 20) => 'u'
+        };
+        segment_map.remove(&Segment::new(5, 25));
+        assert!(segment_map.is_empty());
+
+        // only the side where the query extends beyond the intersection absorbs the empty segment
+        let mut segment_map = segment_map! {
+            Segment::new(10, 10) => 'l',
+            Segment::new(10, 20) => 'a',
+            Segment::new(20, 20) => 'u'
+        };
+        segment_map.remove(&Segment::new(10, 25));
+        assert!(segment_map.validate());
+        assert_eq!(vec![(Segment::new(10, 10), 'l')], segment_map.into_iter().collect::<Vec<_>>());
+
+        // empty segments at the query bounds are not strictly within it, so they remain
+        let mut segment_map = segment_map! {
+            Segment::new(10, 10) => 'l',
+            Segment::new(10, 20) => 'a',
+            Segment::new(20, 20) => 'u'
+        };
+        segment_map.remove(&Segment::new(10, 20));
+        assert_eq!(vec![(Segment::new(10, 10), 'l'), (Segment::new(20, 20), 'u')], segment_map.into_iter().collect::<Vec<_>>());
     }
-}
 
-pub struct Values<'a, K, V> {
-    inner: Iter<'a, K, V>
-}
+    #[test]
+    fn test_update_empty_at_intersection_bound() {
+        let mut segment_map = segment_map! {
+            Segment::new(10, 10) => 'l',
+            Segment::new(10, 20) => 'a',
+            Segment::new(20, 20) => 'u'
+        };
+        segment_map.update(&Segment::new(5, 25), |_| Some('z'));
+        assert!(segment_map.validate());
+        assert!(segment_map.segments().all(|segment| !segment.is_empty()));
+        assert_eq!(Some(&'z'), segment_map.get(&5));
+        assert_eq!(Some(&'z'), segment_map.get(&24));
 
-impl<'a, K, V> Iterator for Values<'a, K, V> {
-    type Item = &'a V;
+        let mut segment_map = segment_map! {
+            Segment::new(10, 10) => 'l',
+            Segment::new(10, 20) => 'a',
+            Segment::new(20, 20) => 'u'
+        };
+        segment_map.update(&Segment::new(15, 25), |_| None);
+        assert!(segment_map.validate());
+        assert_eq!(vec![(Segment::new(10, 10), 'l'), (Segment::new(10, 15), 'a')], segment_map.into_iter().collect::<Vec<_>>());
+    }
 
-    fn next(&mut self) -> Option<&'a V> {
-        self.inner.next().map(|(_, value)| value)
+    #[test]
+    fn test_extend_balanced() {
+        let mut segment_map = SegmentMap::from_sorted((0..127).map(|i| (Segment::new(i, i + 1), i)).collect());
+        let other = SegmentMap::from_sorted((200..327).map(|i| (Segment::new(i, i + 1), i)).collect());
+        assert_eq!(7, segment_map.depth_histogram().len());
+        segment_map.extend_balanced(other);
+        assert!(segment_map.validate());
+        assert_eq!(254, segment_map.len());
+        assert_eq!(8, segment_map.depth_histogram().len());
+        assert_eq!(Some(&126), segment_map.get(&126));
+        assert_eq!(Some(&200), segment_map.get(&200));
+
+        let mut segment_map = segment_map! {
+            Segment::new(10, 20) => 0
+        };
+        segment_map.extend_balanced(segment_map! {
+            Segment::new(0, 5) => 1,
+            Segment::new(5, 10) => 2
+        });
+        segment_map.extend_balanced(segment_map! {
+            Segment::new(-5, 0) => 3,
+            Segment::new(30, 40) => 4
+        });
+        assert!(segment_map.validate());
+        assert_eq!(vec![&3, &1, &2, &0, &4], segment_map.values().collect::<Vec<_>>());
     }
-}
 
-pub struct ValuesMut<'a, K, V> {
-    inner: IterMut<'a, K, V>
-}
+    #[test]
+    fn test_extend_balanced_many() {
+        // appending many small maps keeps the height logarithmic
+        let mut segment_map = SegmentMap::new();
+        for i in 0..1000 {
+            segment_map.extend_balanced(segment_map! {
+                Segment::new(i * 3, i * 3 + 1) => i,
+                Segment::new(i * 3 + 1, i * 3 + 2) => i,
+                Segment::new(i * 3 + 2, i * 3 + 3) => i
+            });
+        }
+        assert!(segment_map.validate());
+        assert_eq!(3000, segment_map.len());
+        assert!(segment_map.depth_histogram().len() <= 20);
+        assert_eq!((0..3000).collect::<Vec<_>>(), segment_map.segments().map(|segment| *segment.lower()).collect::<Vec<_>>());
 
-impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
-    type Item = &'a mut V;
+        // as does prepending them
+        let mut segment_map = SegmentMap::new();
+        for i in (0..1000).rev() {
+            segment_map.extend_balanced(segment_map! {
+                Segment::new(i * 3, i * 3 + 3) => i
+            });
+        }
+        assert!(segment_map.validate());
+        assert_eq!(1000, segment_map.len());
+        assert!(segment_map.depth_histogram().len() <= 16);
+        assert_eq!((0..1000).collect::<Vec<_>>(), segment_map.values().copied().collect::<Vec<_>>());
+    }
 
-    fn next(&mut self) -> Option<&'a mut V> {
-        self.inner.next().map(|(_, value)| value)
+    #[test]
+    fn test_extend_ranges() {
+        let mut segment_map = SegmentMap::new();
+        segment_map.extend(vec![(0..5, 'a'), (5..10, 'b')]);
+        assert_eq!(Some(&'a'), segment_map.get(&4));
+        assert_eq!(Some(&'b'), segment_map.get(&5));
+        assert_eq!(None, segment_map.get(&10));
     }
-}
 
-pub struct Iter<'a, K, V> {
-    current: Option<&'a SegmentMapNode<K, V>>,
-    stack: Vec<(&'a Segment<K>, &'a V, Option<&'a SegmentMapNode<K, V>>)>,
-}
+    #[test]
+    fn test_from_sorted_merge() {
+        let segment_map = SegmentMap::from_sorted_merge(vec![
+            vec![(Segment::new(0, 4), 0), (Segment::new(10, 12), 2), (Segment::new(20, 22), 4)],
+            vec![(Segment::new(4, 8), 1), (Segment::new(14, 16), 3)],
+            vec![],
+        ]);
+        assert!(segment_map.validate());
+        assert_eq!(vec![&0, &1, &2, &3, &4], segment_map.values().collect::<Vec<_>>());
+        assert_eq!(3, segment_map.depth_histogram().len());
+        assert!(std::panic::catch_unwind(|| SegmentMap::from_sorted_merge(vec![
+            vec![(Segment::new(0, 6), 0)],
+            vec![(Segment::new(4, 8), 1)],
+        ])).is_err());
+    }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V> {
-    type Item = (&'a Segment<K>, &'a V);
+    #[test]
+    fn test_from_sorted_checked() {
+        let segment_map = SegmentMap::from_sorted_checked(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 4), 1),
+            (Segment::new(4, 8), 2),
+            (Segment::new(10, 12), 3),
+        ]).unwrap();
+        assert!(segment_map.validate());
+        assert_eq!(vec![&0, &1, &2, &3], segment_map.values().collect::<Vec<_>>());
+        assert_eq!(Err(BuildError::OutOfOrder(2)), SegmentMap::from_sorted_checked(vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(10, 12), 1),
+            (Segment::new(4, 8), 2),
+        ]));
+        assert_eq!(Err(BuildError::Overlapping(1)), SegmentMap::from_sorted_checked(vec![
+            (Segment::new(0, 6), 0),
+            (Segment::new(4, 8), 1),
+        ]));
+        assert_eq!(Err(BuildError::Overlapping(1)), SegmentMap::from_sorted_checked(vec![
+            (Segment::new(4, 4), 0),
+            (Segment::new(4, 4), 1),
+        ]));
+        assert_eq!(Err(BuildError::Inverted(0)), SegmentMap::from_sorted_checked(vec![(Segment::new(4, 0), 0)]));
+    }
 
-    fn next(&mut self) -> Option<(&'a Segment<K>, &'a V)> {
-        while let Some(current) = self.current.take() {
-            self.stack.push((&current.segment, &current.value, (*current.right).as_ref()));
-            self.current = (*current.left).as_ref();
+    #[test]
+    fn test_clear_shrink_to_fit() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1
+        };
+        segment_map.clear();
+        segment_map.shrink_to_fit();
+        assert!(segment_map.is_empty());
+        assert_eq!(0, segment_map.len());
+    }
+
+    #[test]
+    fn test_context() {
+        let segment_map = segment_map! {
+            Segment::new(10, 12) => 2,
+            Segment::new(0, 4) => 0,
+            Segment::new(4, 8) => 1,
+            Segment::new(14, 18) => 3
+        };
+        assert_eq!((
+            Some((&Segment::new(0, 4), &0)),
+            Some((&Segment::new(4, 8), &1)),
+            Some((&Segment::new(10, 12), &2))
+        ), segment_map.context(&5));
+        assert_eq!((
+            Some((&Segment::new(4, 8), &1)),
+            None,
+            Some((&Segment::new(10, 12), &2))
+        ), segment_map.context(&9));
+        assert_eq!((None, Some((&Segment::new(0, 4), &0)), Some((&Segment::new(4, 8), &1))), segment_map.context(&0));
+        assert_eq!((Some((&Segment::new(14, 18), &3)), None, None), segment_map.context(&20));
+    }
+
+    #[test]
+    fn test_get_copied() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 1i32,
+            Segment::new(8, 12) => -2
+        };
+        let mut total = 0;
+        for key in 0..12 {
+            total += segment_map.get_copied(&key).unwrap_or(0);
         }
-        if let Some((segment, value, right)) = self.stack.pop() {
-            self.current = right;
-            Some((segment, value))
-        } else { None }
+        assert_eq!(-2, total);
+        assert_eq!(None, segment_map.get_copied(&7));
     }
-}
 
-pub struct IterMut<'a, K, V> {
-    current: Option<&'a mut SegmentMapNode<K, V>>,
-    stack: Vec<(&'a Segment<K>, &'a mut V, Option<&'a mut SegmentMapNode<K, V>>)>,
-}
+    #[test]
+    fn test_get_segment_copied() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 'a',
+            Segment::new(8, 12) => 'b'
+        };
+        let segment = segment_map.get_segment_copied(&9);
+        drop(segment_map);
+        assert_eq!(Some(Segment::new(8, 12)), segment);
+    }
 
-impl<'a, K, V> Iterator for IterMut<'a, K, V> {
-    type Item = (&'a Segment<K>, &'a mut V);
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_assert_entries() {
+        let segment_map = segment_map! {
+            Segment::new(0, 6) => 0,
+            Segment::new(8, 12) => 1
+        };
+        segment_map.assert_entries(&[(Segment::new(0, 6), 0), (Segment::new(8, 12), 1)]);
+        let message = std::panic::catch_unwind(|| segment_map.assert_entries(&[(Segment::new(0, 6), 0), (Segment::new(8, 12), 2)]))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert_eq!(concat!(
+            "entries differ:\n",
+            "  actual                               | expected\n",
+            "  Segment { lower: 0, upper: 6 } => 0  | Segment { lower: 0, upper: 6 } => 0\n",
+            "! Segment { lower: 8, upper: 12 } => 1 | Segment { lower: 8, upper: 12 } => 2\n",
+        ), *message);
+        assert!(std::panic::catch_unwind(|| segment_map.assert_entries(&[(Segment::new(0, 6), 0)])).is_err());
+    }
 
-    fn next(&mut self) -> Option<(&'a Segment<K>, &'a mut V)> {
-        while let Some(current) = self.current.take() {
-            self.stack.push((&current.segment, &mut current.value, (*current.right).as_mut()));
-            self.current = (*current.left).as_mut();
+    #[test]
+    fn test_content_ordering() {
+        // same contents, different tree shapes
+        let mut forward = SegmentMap::new();
+        let mut backward = SegmentMap::new();
+        for key in 0..4 {
+            forward.insert(Segment::new(key, key + 1), key);
+            backward.insert(Segment::new(3 - key, 4 - key), 3 - key);
         }
-        if let Some((segment, value, right)) = self.stack.pop() {
-            self.current = right;
-            Some((segment, value))
-        } else { None }
+        let mut smaller = forward.clone();
+        smaller.insert(Segment::new(4, 5), 0);
+        smaller.remove(&Segment::new(0, 1));
+
+        let mut segment_maps = BTreeMap::new();
+        segment_maps.insert(forward.clone(), 'a');
+        segment_maps.insert(backward.clone(), 'b');
+        segment_maps.insert(smaller.clone(), 'c');
+        assert_eq!(forward, backward);
+        assert!(forward < smaller);
+        assert_eq!(vec![
+            (&forward, &'b'),
+            (&smaller, &'c')
+        ], segment_maps.iter().collect::<Vec<_>>());
     }
-}
 
-impl<K, V> Extend<(Segment<K>, V)> for SegmentMap<K, V> 
-where
-    K: Clone + PartialOrd,
-    V: Clone,
-{
-    fn extend<I>(&mut self, iter: I) 
-    where
-        I: IntoIterator<Item = (Segment<K>, V)>
-    {
-        for (segment, value) in iter {
-            self.insert(segment, value);
+    #[test]
+    fn test_remove_min_node_keeps_right() {
+        // removing the root replaces it with the right minimum, [15, 16), whose right child
+        // [17, 18) must move up rather than be dropped
+        let mut segment_map = SegmentMap::new();
+        for key in [10, 5, 20, 15, 17] {
+            segment_map.insert(Segment::new(key, key + 1), key);
         }
-    }
-}
+        segment_map.remove(&Segment::new(10, 11));
+        assert!(segment_map.validate());
+        assert_eq!(vec![&5, &15, &17, &20], segment_map.values().collect::<Vec<_>>());
 
-impl<K, V> IntoIterator for SegmentMap<K, V> {
-    type Item = (Segment<K>, V);
-    type IntoIter = IntoIter<K, V>;
+        let (rest, min_node) = SegmentMapNode::new(Segment::new(0, 1), 0, None, Some(SegmentMapNode::new(Segment::new(1, 2), 1, None, None))).remove_min_node();
+        assert_eq!(Segment::new(0, 1), min_node.segment);
+        assert_eq!(Some(Segment::new(1, 2)), rest.map(|rest| rest.segment));
+    }
 
-    fn into_iter(self) -> IntoIter<K, V> {
-        IntoIter {
-            current: self.root,
-            stack: Vec::new(),
+    #[test]
+    fn test_pop_min_max() {
+        let mut segment_map = SegmentMap::new();
+        for key in [5, 2, 8, 1, 3, 7, 9, 0, 4, 6] {
+            segment_map.insert(Segment::new(key, key + 1), key);
         }
-    }
-}
+        let mut popped = Vec::new();
+        while let Some((segment, value)) = segment_map.pop_min() {
+            assert!(segment_map.validate());
+            assert_eq!(9 - value as usize, segment_map.len());
+            popped.push((segment, value));
+        }
+        assert_eq!((0..10).map(|key| (Segment::new(key, key + 1), key)).collect::<Vec<_>>(), popped);
 
-pub struct IntoIter<K, V> {
-    current: Option<SegmentMapNode<K, V>>,
-    stack: Vec<(Segment<K>, V, Option<SegmentMapNode<K, V>>)>,
-}
+        let mut segment_map = SegmentMap::from_sorted((0..10).map(|key| (Segment::new(key, key + 1), key)).collect());
+        assert_eq!(Some((Segment::new(9, 10), 9)), segment_map.pop_max());
+        assert_eq!(Some((Segment::new(8, 9), 8)), segment_map.pop_max());
+        assert!(segment_map.validate());
+        assert_eq!(Some((Segment::new(0, 1), 0)), segment_map.pop_min());
+        assert_eq!(7, segment_map.len());
+    }
 
-impl<K, V> Iterator for IntoIter<K, V> {
-    type Item = (Segment<K>, V);
+    #[test]
+    fn test_pop_min_degenerate() {
+        // a left spine deep enough to overflow the stack if descended recursively
+        let mut segment_map = SegmentMap::new();
+        for key in 0..100_000 {
+            segment_map.root = Some(SegmentMapNode::new(Segment::new(key, key + 1), key, segment_map.root.take(), None));
+        }
+        assert_eq!(Some((Segment::new(0, 1), 0)), segment_map.pop_min());
+        assert_eq!(Some((Segment::new(1, 2), 1)), segment_map.pop_min());
+        assert_eq!(99_998, segment_map.len());
+    }
 
-    fn next(&mut self) -> Option<(Segment<K>, V)> {
-        while let Some(current) = self.current.take() {
-            self.stack.push((current.segment, current.value, *current.right));
-            self.current = *current.left;
+    #[test]
+    fn test_full() {
+        let mut segment_map = SegmentMap::full(0);
+        for key in [i32::MIN, -1, 0, 1, i32::MAX - 1] {
+            assert_eq!(Some(&0), segment_map.get(&key));
         }
-        if let Some((segment, value, right)) = self.stack.pop() {
-            self.current = right;
-            Some((segment, value))
-        } else { None }
+        segment_map.update(&Segment::new(-5, 5), |_| None);
+        assert_eq!(vec![
+            (Segment::new(i32::MIN, -5), 0),
+            (Segment::new(5, i32::MAX), 0)
+        ], segment_map.into_iter().collect::<Vec<_>>());
     }
-}
 
-#[macro_export]
-macro_rules! segment_map {
-    ($($x:expr => $y:expr),*) => {{
-        #[allow(unused_mut)]
-        let mut temp_segment_map = $crate::SegmentMap::new();
-        $(temp_segment_map.insert($x, $y);)*
-        temp_segment_map
-    }}
-}
+    #[test]
+    fn test_retain_map() {
+        let mut segment_map = segment_map! {
+            Segment::new(0, 2) => 3,
+            Segment::new(2, 4) => 1,
+            Segment::new(5, 6) => 2,
+            Segment::new(7, 8) => 1
+        };
+        segment_map.retain_map(|_, value| {
+            *value -= 1;
+            *value > 0
+        });
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(0, 2), 2),
+            (Segment::new(5, 6), 1)
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        Segment,
-        SegmentMap,
-    };
+    #[test]
+    fn test_remove_empty_strictly_within() {
+        // the empty root lies strictly within the removed segment, which extends to both sides
+        let mut segment_map = SegmentMap::new();
+        segment_map.insert(Segment::new(7, 7), 'e');
+        segment_map.insert(Segment::new(0, 5), 'a');
+        segment_map.insert(Segment::new(10, 15), 'b');
+        segment_map.remove(&Segment::new(3, 12));
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(0, 3), 'a'),
+            (Segment::new(12, 15), 'b')
+        ], segment_map.into_iter().collect::<Vec<_>>());
+    }
 
     #[test]
-    fn test_insert_multiple_empty() {
+    fn test_update_empty_strictly_within() {
         let mut segment_map = SegmentMap::new();
-        segment_map.insert(Segment::new(0, 1), 0);
-        segment_map.insert(Segment::new(1, 1), 1);
-        assert!(std::panic::catch_unwind(move || segment_map.insert(Segment::new(1, 1), 2)).is_err());
+        segment_map.insert(Segment::new(7, 7), 'e');
+        segment_map.insert(Segment::new(0, 5), 'a');
+        segment_map.insert(Segment::new(10, 15), 'b');
+        segment_map.update(&Segment::new(3, 12), |_| Some('z'));
+        assert!(segment_map.validate());
+        assert_eq!(vec![
+            (Segment::new(0, 3), 'a'),
+            (Segment::new(3, 5), 'z'),
+            (Segment::new(5, 10), 'z'),
+            (Segment::new(10, 12), 'z'),
+            (Segment::new(12, 15), 'b')
+        ], segment_map.clone().into_iter().collect::<Vec<_>>());
+        segment_map.insert(Segment::new(20, 20), 'e');
+        segment_map.update(&Segment::new(16, 25), |_| None);
+        assert_eq!(5, segment_map.len());
     }
 
     #[test]
@@ -627,6 +4443,22 @@ mod tests {
                     (Segment::new(0, 6), 1),
                     (Segment::new(6, 12), 2)
                 ],
+            ), (
+                format!("{}\n{}\n{}\n",
+                    "  --[-----------)----",
+                    "                      -> [0)-----------[2--)",
+                    "  [0--)-|-----[2----)"
+                ),
+                Segment::new(2, 14),
+                vec![
+                    (Segment::new(0, 4), 0),
+                    (Segment::new(6, 6), 1),
+                    (Segment::new(12, 18), 2)
+                ],
+                vec![
+                    (Segment::new(0, 2), 0),
+                    (Segment::new(14, 18), 2)
+                ],
             )
 
         ];
@@ -645,7 +4477,7 @@ mod tests {
 
     #[test]
     fn test_update() {
-        let permutations = vec![
+        let permutations = [
             vec![
             ], vec![(
                     format!("{}\n",
@@ -1384,6 +5216,25 @@ mod tests {
                     (Segment::new(0, 6), 1),
                     (Segment::new(6, 12), 2)
                 ],
+            ), (
+                format!("{}\n{}\n{}\n",
+                    "  --[3----------)----",
+                    "                      -> [0)[3|3------|3)[2--)",
+                    "  [0--)-|-----[2----)"
+                ),
+                (Segment::new(2, 14), 3),
+                vec![
+                    (Segment::new(0, 4), 0),
+                    (Segment::new(6, 6), 1),
+                    (Segment::new(12, 18), 2)
+                ],
+                vec![
+                    (Segment::new(0, 2), 0),
+                    (Segment::new(2, 4), 3),
+                    (Segment::new(4, 12), 3),
+                    (Segment::new(12, 14), 3),
+                    (Segment::new(14, 18), 2)
+                ],
             )
         ];
         for (case_description, update_segment, insert_segments, expected_segments) in cases {
@@ -1402,7 +5253,7 @@ mod tests {
 
     #[test]
     fn test_update_remove() {
-        let permutations = vec![
+        let permutations = [
             vec![
             ], vec![(
                     format!("{}\n",
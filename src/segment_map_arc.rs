@@ -0,0 +1,541 @@
+use crate::{
+    OverlapError,
+    Segment,
+    SegmentMap,
+};
+use std::{
+    fmt,
+    sync::Arc,
+};
+
+#[derive(Clone)]
+struct SegmentMapArcNode<K, V> {
+    segment: Segment<K>,
+    value: V,
+    left: Option<Arc<SegmentMapArcNode<K, V>>>,
+    right: Option<Arc<SegmentMapArcNode<K, V>>>,
+}
+
+/// A persistent `SegmentMap` whose nodes are shared between snapshots. Mutations copy
+/// only the path from the root to the changed nodes, so untouched subtrees stay shared.
+pub struct SegmentMapArc<K, V> {
+    root: Option<Arc<SegmentMapArcNode<K, V>>>,
+    len: usize,
+}
+
+impl<K, V> SegmentMapArc<K, V> {
+    pub fn new() -> SegmentMapArc<K, V> {
+        SegmentMapArc { root: None, len: 0 }
+    }
+
+    /// Returns a copy of the map in O(1). The copy shares every node with `self` until one
+    /// of them is mutated.
+    pub fn snapshot(&self) -> SegmentMapArc<K, V> {
+        SegmentMapArc { root: self.root.clone(), len: self.len }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Removes every entry. Nodes shared with a snapshot stay alive in the snapshot.
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    /// Iterates over the entries in ascending order of segment.
+    pub fn iter(&self) -> SegmentMapArcIter<'_, K, V> {
+        SegmentMapArcIter { current: self.root.as_deref(), stack: Vec::new() }
+    }
+
+    pub fn min_segment(&self) -> Option<&Segment<K>> {
+        self.min_node().map(|node| &node.segment)
+    }
+
+    pub fn min_value(&self) -> Option<&V> {
+        self.min_node().map(|node| &node.value)
+    }
+
+    pub fn max_segment(&self) -> Option<&Segment<K>> {
+        self.max_node().map(|node| &node.segment)
+    }
+
+    pub fn max_value(&self) -> Option<&V> {
+        self.max_node().map(|node| &node.value)
+    }
+
+    fn min_node(&self) -> Option<&SegmentMapArcNode<K, V>> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(node)
+    }
+
+    fn max_node(&self) -> Option<&SegmentMapArcNode<K, V>> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some(node)
+    }
+}
+
+impl<K, V> SegmentMapArc<K, V>
+where
+    K: PartialOrd
+{
+    /// Returns the value of the segment containing `key`.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.get_entry(key).map(|(_, value)| value)
+    }
+
+    /// Returns the segment containing `key` together with its value.
+    pub fn get_entry(&self, key: &K) -> Option<(&Segment<K>, &V)> {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            // if node segment contains key
+            if node.segment.contains(key) {
+                return Some((&node.segment, &node.value));
+            // if key is less than node segment, descend left
+            } else if key < node.segment.lower() {
+                current = node.left.as_deref();
+            // otherwise, key is greater than node segment, descend right
+            } else {
+                current = node.right.as_deref();
+            }
+        }
+        // key doesn't exist
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get_entry(key).is_some()
+    }
+}
+
+impl<K, V> SegmentMapArc<K, V>
+where
+    K: Clone + PartialOrd,
+    V: Clone,
+{
+    /// Inserts `segment` with `value`, copying the nodes on its path that are shared with a
+    /// snapshot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `segment` overlaps an existing segment.
+    pub fn insert(&mut self, segment: Segment<K>, value: V) {
+        if self.try_insert(segment, value).is_err() {
+            panic!("segments must not overlap");
+        }
+    }
+
+    /// Like `insert`, but returns the segment and value instead of panicking on overlap.
+    pub fn try_insert(&mut self, segment: Segment<K>, value: V) -> Result<(), OverlapError<K, V>> {
+        // check before copying so a failed insert leaves shared nodes untouched
+        if self.overlaps(&segment) {
+            return Err(OverlapError::new(segment, value));
+        }
+        let mut link = &mut self.root;
+        while let Some(node) = link {
+            // copy node if it is shared with a snapshot
+            let node = Arc::make_mut(node);
+            // if segment is less than node segment, descend left
+            if segment.upper() <= node.segment.lower() {
+                link = &mut node.left;
+            // otherwise, segment is greater than node segment, descend right
+            } else {
+                link = &mut node.right;
+            }
+        }
+        *link = Some(Arc::new(SegmentMapArcNode { segment, value, left: None, right: None }));
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes `segment` from the map, trimming the segments it partially overlaps.
+    pub fn remove(&mut self, segment: &Segment<K>) {
+        debug_assert!(segment.lower() <= segment.upper(), "segment must not be inverted");
+        // inverted segments are treated as a no-op
+        if segment.lower() > segment.upper() {
+            return;
+        }
+        let mut affected = Vec::new();
+        SegmentMapArc::collect_affected(self.root.as_deref(), segment, &mut affected);
+        for (existing, value) in affected {
+            SegmentMapArc::delete(&mut self.root, &existing);
+            self.len -= 1;
+            // if left part of existing segment remains, reinsert
+            if existing.lower() < segment.lower() {
+                self.insert(Segment::new(existing.lower().clone(), segment.lower().clone()), value.clone());
+            }
+            // if right part of existing segment remains, reinsert
+            if existing.upper() > segment.upper() {
+                self.insert(Segment::new(segment.upper().clone(), existing.upper().clone()), value);
+            }
+        }
+    }
+
+    /// Sets the values within `segment` to the result of `value`, which receives the current
+    /// value of each piece of `segment` (or `None` for gaps). Pieces for which `value` returns
+    /// `None` are removed. Behaves like `SegmentMap::update`.
+    pub fn update<F>(&mut self, segment: &Segment<K>, value: F)
+    where
+        F: Fn(Option<V>) -> Option<V> + Clone
+    {
+        debug_assert!(segment.lower() <= segment.upper(), "segment must not be inverted");
+        // inverted segments are treated as a no-op
+        if segment.lower() > segment.upper() {
+            return;
+        }
+        let mut affected = Vec::new();
+        SegmentMapArc::collect_affected(self.root.as_deref(), segment, &mut affected);
+        let mut pieces = Vec::new();
+        let mut position = segment.lower().clone();
+        for (existing, existing_value) in affected {
+            SegmentMapArc::delete(&mut self.root, &existing);
+            self.len -= 1;
+            // empty segments strictly within segment are absorbed by it
+            if existing.is_empty() && (existing != *segment) {
+                continue;
+            }
+            // if left part of existing segment remains, reinsert
+            if existing.lower() < segment.lower() {
+                self.insert(Segment::new(existing.lower().clone(), segment.lower().clone()), existing_value.clone());
+            }
+            // if right part of existing segment remains, reinsert
+            if existing.upper() > segment.upper() {
+                self.insert(Segment::new(segment.upper().clone(), existing.upper().clone()), existing_value.clone());
+            }
+            let lower = if existing.lower() > segment.lower() { existing.lower() } else { segment.lower() };
+            let upper = if existing.upper() < segment.upper() { existing.upper() } else { segment.upper() };
+            // if there is a gap before the existing segment, it is updated from nothing
+            if &position < lower {
+                pieces.push((Segment::new(position, lower.clone()), None));
+            }
+            position = upper.clone();
+            pieces.push((Segment::new(lower.clone(), upper.clone()), Some(existing_value)));
+        }
+        // if segment extends beyond all existing segments (or is an empty segment touching
+        // none), the rest is updated from nothing
+        if (&position < segment.upper()) || pieces.is_empty() {
+            pieces.push((Segment::new(position, segment.upper().clone()), None));
+        }
+        for (piece, old_value) in pieces {
+            if let Some(new_value) = value(old_value) {
+                self.insert(piece, new_value);
+            }
+        }
+    }
+
+    fn overlaps(&self, segment: &Segment<K>) -> bool {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            // if the segments perfectly overlap (this prevents inserting duplicate empty segments)
+            if (segment.lower() == node.segment.lower()) && (segment.upper() == node.segment.upper()) {
+                return true;
+            // if segment is less than node segment, descend left
+            } else if segment.upper() <= node.segment.lower() {
+                current = node.left.as_deref();
+            // if segment is greater than node segment, descend right
+            } else if segment.lower() >= node.segment.upper() {
+                current = node.right.as_deref();
+            // otherwise, segments overlap in some (non-perfect) way
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn collect_affected(node: Option<&SegmentMapArcNode<K, V>>, segment: &Segment<K>, affected: &mut Vec<(Segment<K>, V)>) {
+        if let Some(node) = node {
+            // left segments all end before node segment starts
+            if node.segment.lower() >= segment.lower() {
+                SegmentMapArc::collect_affected(node.left.as_deref(), segment, affected);
+            }
+            let existing = &node.segment;
+            let overlapping = (existing.lower() < segment.upper()) && (segment.lower() < existing.upper());
+            let strictly_within = |inner: &Segment<K>, outer: &Segment<K>| {
+                inner.is_empty() && (outer.lower() < inner.lower()) && (inner.upper() < outer.upper())
+            };
+            if (existing == segment) || overlapping || strictly_within(existing, segment) || strictly_within(segment, existing) {
+                affected.push((existing.clone(), node.value.clone()));
+            }
+            // right segments all start after node segment ends
+            if node.segment.upper() <= segment.upper() {
+                SegmentMapArc::collect_affected(node.right.as_deref(), segment, affected);
+            }
+        }
+    }
+
+    fn delete(link: &mut Option<Arc<SegmentMapArcNode<K, V>>>, segment: &Segment<K>) {
+        // if link is empty, nothing to delete
+        let node = match link {
+            Some(node) => Arc::make_mut(node),
+            None => return,
+        };
+        // if segment is less than node segment, recurse left
+        if (node.segment != *segment) && (segment.upper() <= node.segment.lower()) {
+            SegmentMapArc::delete(&mut node.left, segment);
+        // if segment is greater than node segment, recurse right
+        } else if node.segment != *segment {
+            SegmentMapArc::delete(&mut node.right, segment);
+        // otherwise, delete node
+        } else {
+            match (node.left.take(), node.right.take()) {
+                // two children, replace with right minimum
+                (Some(left), Some(right)) => {
+                    let mut right = Some(right);
+                    let (segment, value) = SegmentMapArc::remove_min(&mut right);
+                    node.segment = segment;
+                    node.value = value;
+                    node.left = Some(left);
+                    node.right = right;
+                },
+                // one child, move up
+                (Some(child), None) | (None, Some(child)) => *link = Some(child),
+                // no children, remove
+                (None, None) => *link = None,
+            }
+        }
+    }
+
+    fn remove_min(link: &mut Option<Arc<SegmentMapArcNode<K, V>>>) -> (Segment<K>, V) {
+        let mut link = link;
+        // descend to the minimum, copying the path
+        while link.as_ref().is_some_and(|node| node.left.is_some()) {
+            link = &mut Arc::make_mut(link.as_mut().expect("node exists")).left;
+        }
+        let node = link.take().expect("node exists");
+        let node = Arc::try_unwrap(node).unwrap_or_else(|node| (*node).clone());
+        *link = node.right;
+        (node.segment, node.value)
+    }
+}
+
+impl<K, V> SegmentMapArcNode<K, V> {
+    fn from_sorted<I>(entries: &mut I, len: usize) -> Option<Arc<SegmentMapArcNode<K, V>>>
+    where
+        I: Iterator<Item = (Segment<K>, V)>
+    {
+        // if no entries remain, no node
+        if len == 0 {
+            None
+        // otherwise, build left half, then middle, then right half
+        } else {
+            let left = SegmentMapArcNode::from_sorted(entries, len / 2);
+            let (segment, value) = entries.next().expect("too few entries");
+            let right = SegmentMapArcNode::from_sorted(entries, len - len / 2 - 1);
+            Some(Arc::new(SegmentMapArcNode { segment, value, left, right }))
+        }
+    }
+}
+
+impl<K, V> Clone for SegmentMapArc<K, V> {
+    fn clone(&self) -> SegmentMapArc<K, V> {
+        self.snapshot()
+    }
+}
+
+impl<K, V> Default for SegmentMapArc<K, V> {
+    fn default() -> SegmentMapArc<K, V> {
+        SegmentMapArc::new()
+    }
+}
+
+impl<K, V> fmt::Debug for SegmentMapArc<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> From<SegmentMap<K, V>> for SegmentMapArc<K, V>
+where
+    K: Clone + PartialOrd,
+    V: Clone,
+{
+    fn from(segment_map: SegmentMap<K, V>) -> SegmentMapArc<K, V> {
+        let len = segment_map.len();
+        SegmentMapArc { root: SegmentMapArcNode::from_sorted(&mut segment_map.into_iter(), len), len }
+    }
+}
+
+impl<K, V> Drop for SegmentMapArc<K, V> {
+    fn drop(&mut self) {
+        // dismantle uniquely owned nodes iteratively so degenerate trees don't overflow the
+        // stack, nodes shared with a snapshot are left to it
+        let mut stack = self.root.take().into_iter().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if let Ok(mut node) = Arc::try_unwrap(node) {
+                stack.extend(node.left.take());
+                stack.extend(node.right.take());
+            }
+        }
+    }
+}
+
+pub struct SegmentMapArcIter<'a, K, V> {
+    current: Option<&'a SegmentMapArcNode<K, V>>,
+    stack: Vec<&'a SegmentMapArcNode<K, V>>,
+}
+
+impl<'a, K, V> Iterator for SegmentMapArcIter<'a, K, V> {
+    type Item = (&'a Segment<K>, &'a V);
+
+    fn next(&mut self) -> Option<(&'a Segment<K>, &'a V)> {
+        while let Some(current) = self.current.take() {
+            self.stack.push(current);
+            self.current = current.left.as_deref();
+        }
+        let node = self.stack.pop()?;
+        self.current = node.right.as_deref();
+        Some((&node.segment, &node.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SegmentMapArcNode;
+    use crate::{
+        Segment,
+        SegmentMap,
+        SegmentMapArc,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshot() {
+        let mut segment_map = SegmentMapArc::new();
+        for key in [3, 1, 5, 0, 2, 4, 6] {
+            segment_map.insert(Segment::new(key * 10, key * 10 + 10), key);
+        }
+        let snapshot = segment_map.snapshot();
+        segment_map.insert(Segment::new(70, 80), 7);
+        segment_map.remove(&Segment::new(45, 55));
+
+        assert_eq!(vec![
+            (&Segment::new(0, 10), &0),
+            (&Segment::new(10, 20), &1),
+            (&Segment::new(20, 30), &2),
+            (&Segment::new(30, 40), &3),
+            (&Segment::new(40, 50), &4),
+            (&Segment::new(50, 60), &5),
+            (&Segment::new(60, 70), &6),
+        ], snapshot.iter().collect::<Vec<_>>());
+        assert_eq!(vec![
+            (&Segment::new(0, 10), &0),
+            (&Segment::new(10, 20), &1),
+            (&Segment::new(20, 30), &2),
+            (&Segment::new(30, 40), &3),
+            (&Segment::new(40, 45), &4),
+            (&Segment::new(55, 60), &5),
+            (&Segment::new(60, 70), &6),
+            (&Segment::new(70, 80), &7),
+        ], segment_map.iter().collect::<Vec<_>>());
+
+        // the untouched left subtree is shared, the edited root is not
+        let left = segment_map.root.as_ref().unwrap().left.as_ref().unwrap();
+        assert_eq!(2, Arc::strong_count(left));
+        assert!(Arc::ptr_eq(left, snapshot.root.as_ref().unwrap().left.as_ref().unwrap()));
+        assert_eq!(1, Arc::strong_count(segment_map.root.as_ref().unwrap()));
+        assert_eq!(1, Arc::strong_count(snapshot.root.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn test_from_large() {
+        let segment_map = SegmentMap::from_sorted_checked((0..100_000).map(|key| (Segment::new(key, key + 1), key)).collect()).unwrap();
+        let segment_map_arc = SegmentMapArc::from(segment_map);
+        let snapshot = segment_map_arc.snapshot();
+        assert_eq!(Some(&50_000), segment_map_arc.get(&50_000));
+        drop(segment_map_arc);
+        assert_eq!(100_000, snapshot.iter().count());
+        assert_eq!(100_000, snapshot.len());
+
+        // a degenerate chain drops without overflowing the stack
+        let mut segment_map_arc = SegmentMapArc::new();
+        for key in 0..100_000 {
+            segment_map_arc.root = Some(Arc::new(SegmentMapArcNode {
+                segment: Segment::new(key, key + 1),
+                value: key,
+                left: segment_map_arc.root.take(),
+                right: None,
+            }));
+        }
+        drop(segment_map_arc);
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut segment_map = SegmentMapArc::new();
+        segment_map.insert(Segment::new(0, 10), 0);
+        segment_map.insert(Segment::new(12, 14), 1);
+        assert!(segment_map.try_insert(Segment::new(5, 13), 2).is_err());
+        segment_map.remove(&Segment::new(4, 6));
+        assert_eq!(Some(&0), segment_map.get(&3));
+        assert_eq!(None, segment_map.get(&5));
+        assert_eq!(Some(&0), segment_map.get(&6));
+        segment_map.remove(&Segment::new(0, 20));
+        assert!(segment_map.is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let mut segment_map = SegmentMapArc::new();
+        segment_map.insert(Segment::new(0, 10), 0);
+        segment_map.insert(Segment::new(12, 12), 1);
+        segment_map.insert(Segment::new(15, 20), 2);
+        let snapshot = segment_map.snapshot();
+        segment_map.update(&Segment::new(5, 17), |value| Some(value.map_or(10, |value| value + 5)));
+
+        let mut expected = SegmentMap::new();
+        expected.insert(Segment::new(0, 10), 0);
+        expected.insert(Segment::new(12, 12), 1);
+        expected.insert(Segment::new(15, 20), 2);
+        expected.update(&Segment::new(5, 17), |value| Some(value.map_or(10, |value| value + 5)));
+
+        assert_eq!(expected.iter().collect::<Vec<_>>(), segment_map.iter().collect::<Vec<_>>());
+        assert_eq!(vec![
+            (&Segment::new(0, 5), &0),
+            (&Segment::new(5, 10), &5),
+            (&Segment::new(10, 15), &10),
+            (&Segment::new(15, 17), &7),
+            (&Segment::new(17, 20), &2),
+        ], segment_map.iter().collect::<Vec<_>>());
+        assert_eq!(5, segment_map.len());
+        assert_eq!(3, snapshot.len());
+
+        segment_map.update(&Segment::new(3, 3), |_| Some(9));
+        assert_eq!(Some(&Segment::new(3, 3)), segment_map.iter().nth(1).map(|(segment, _)| segment));
+        segment_map.update(&Segment::new(0, 20), |_| None);
+        assert!(segment_map.is_empty());
+        assert_eq!(0, segment_map.len());
+    }
+
+    #[test]
+    fn test_min_max() {
+        let mut segment_map = SegmentMapArc::new();
+        assert_eq!(None, segment_map.min_segment());
+        assert_eq!(None, segment_map.max_value());
+        for key in [3, 1, 5, 0, 2, 4, 6] {
+            segment_map.insert(Segment::new(key * 10, key * 10 + 10), key);
+        }
+        assert_eq!(Some(&Segment::new(0, 10)), segment_map.min_segment());
+        assert_eq!(Some(&0), segment_map.min_value());
+        assert_eq!(Some(&Segment::new(60, 70)), segment_map.max_segment());
+        assert_eq!(Some(&6), segment_map.max_value());
+        assert_eq!(7, segment_map.len());
+        segment_map.clear();
+        assert_eq!(0, segment_map.len());
+    }
+}
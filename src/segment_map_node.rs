@@ -1,11 +1,36 @@
-use crate::Segment;
+use crate::{
+    IntersectKind,
+    OverlapError,
+    Segment,
+};
 
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct SegmentMapNode<K, V> {
     pub segment: Segment<K>,
     pub value: V,
     pub left: Box<Option<SegmentMapNode<K, V>>>,
-    pub right: Box<Option<SegmentMapNode<K, V>>>
+    pub right: Box<Option<SegmentMapNode<K, V>>>,
+    pub size: usize,
+}
+
+impl<K, V> SegmentMapNode<K, V> {
+    pub fn entry(&self) -> (&Segment<K>, &V) {
+        (&self.segment, &self.value)
+    }
+
+    pub fn size_of(node: &Option<SegmentMapNode<K, V>>) -> usize {
+        node.as_ref().map_or(0, |node| node.size)
+    }
+
+    // recompute size from children, which must already be sized
+    pub fn resize(&mut self) {
+        self.size = 1 + SegmentMapNode::size_of(&self.left) + SegmentMapNode::size_of(&self.right);
+    }
+
+    pub fn resized(mut self) -> SegmentMapNode<K, V> {
+        self.resize();
+        self
+    }
 }
 
 impl<K, V> SegmentMapNode<K, V> 
@@ -13,11 +38,29 @@ where
     K: PartialOrd
 {
     pub fn new(segment: Segment<K>, value: V, left: Option<SegmentMapNode<K, V>>, right: Option<SegmentMapNode<K, V>>) -> SegmentMapNode<K, V> {
+        let size = 1 + SegmentMapNode::size_of(&left) + SegmentMapNode::size_of(&right);
         SegmentMapNode {
             segment,
             value,
             left: Box::new(left),
             right: Box::new(right),
+            size,
+        }
+    }
+
+    pub fn from_sorted<I>(entries: &mut I, len: usize) -> Option<SegmentMapNode<K, V>>
+    where
+        I: Iterator<Item = (Segment<K>, V)>
+    {
+        // if no entries remain, no node
+        if len == 0 {
+            None
+        // otherwise, build left half, then middle, then right half
+        } else {
+            let left = SegmentMapNode::from_sorted(entries, len / 2);
+            let (segment, value) = entries.next().expect("too few entries");
+            let right = SegmentMapNode::from_sorted(entries, len - len / 2 - 1);
+            Some(SegmentMapNode::new(segment, value, left, right))
         }
     }
 
@@ -34,13 +77,37 @@ where
     }
 
     pub fn remove_min_node(mut self) -> (Option<SegmentMapNode<K, V>>, SegmentMapNode<K, V>) {
-        // if left exists, recurse
-        if let Some(left) = self.left.take() {
-            let (left, min_node) = left.remove_min_node();
-            self.left = Box::new(left);
-            (Some(self), min_node)
-        // otherwise, self is minimum
-        } else { (None, self) }
+        // detach the left spine iteratively so degenerate trees don't overflow the stack
+        let mut spine = Vec::new();
+        while let Some(left) = self.left.take() {
+            spine.push(self);
+            self = left;
+        }
+        // self is minimum, right takes its place
+        let mut result = self.right.take();
+        // reattach the spine, resizing on the way up
+        while let Some(mut node) = spine.pop() {
+            node.left = Box::new(result);
+            result = Some(node.resized());
+        }
+        (result, self)
+    }
+
+    pub fn remove_max_node(mut self) -> (Option<SegmentMapNode<K, V>>, SegmentMapNode<K, V>) {
+        // detach the right spine iteratively so degenerate trees don't overflow the stack
+        let mut spine = Vec::new();
+        while let Some(right) = self.right.take() {
+            spine.push(self);
+            self = right;
+        }
+        // self is maximum, left takes its place
+        let mut result = self.left.take();
+        // reattach the spine, resizing on the way up
+        while let Some(mut node) = spine.pop() {
+            node.right = Box::new(result);
+            result = Some(node.resized());
+        }
+        (result, self)
     }
 
     pub fn max_key(&self) -> &K {
@@ -64,51 +131,155 @@ where
     }
 
     pub fn get_entry(&self, key: &K) -> Option<(&Segment<K>, &V)> {
-        // if self segment contains key
-        if self.segment.contains(key) {
-            Some((&self.segment, &self.value))
-        // if key is less than self segment
-        } else if key < self.segment.lower() {
+        let mut current = Some(self);
+        while let Some(node) = current {
+            // if node segment contains key
+            if node.segment.contains(key) {
+                return Some((&node.segment, &node.value));
+            // if key is less than node segment, descend left
+            } else if key < node.segment.lower() {
+                current = (*node.left).as_ref();
+            // otherwise, key is greater than node segment, descend right
+            } else {
+                current = (*node.right).as_ref();
+            }
+        }
+        // key doesn't exist
+        None
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let mut current = Some(self);
+        while let Some(node) = current {
+            // if node segment contains key
+            if node.segment.contains(key) {
+                return Some(&mut node.value);
+            // if key is less than node segment, descend left
+            } else if key < node.segment.lower() {
+                current = (*node.left).as_mut();
+            // otherwise, key is greater than node segment, descend right
+            } else {
+                current = (*node.right).as_mut();
+            }
+        }
+        // key doesn't exist
+        None
+    }
+
+    pub fn get_entry_ending_at(&self, upper: &K) -> Option<(&Segment<K>, &V)> {
+        let mut current = Some(self);
+        while let Some(node) = current {
+            // if node segment is nonempty and ends at upper
+            if (node.segment.upper() == upper) && !node.segment.is_empty() {
+                return Some((&node.segment, &node.value));
+            // if upper is at or before node segment, descend left
+            } else if upper <= node.segment.lower() {
+                current = (*node.left).as_ref();
+            // otherwise, upper is after node segment lower, descend right
+            } else {
+                current = (*node.right).as_ref();
+            }
+        }
+        // no segment ends at upper
+        None
+    }
+
+    pub fn overlaps(&self, segment: &Segment<K>) -> bool {
+        // empty segments never overlap
+        if segment.is_empty() {
+            false
+        // if segment is less than self segment
+        } else if segment.upper() <= self.segment.lower() {
             // if left exists, recurse
             if let Some(left) = self.left.as_ref() {
-                left.get_entry(key)
-            // otherwise, key doesn't exist
-            } else { None }
-        // otherwise, key is greater than self segment
-        } else {
+                left.overlaps(segment)
+            // otherwise, nothing overlaps
+            } else { false }
+        // if segment is greater than self segment
+        } else if segment.lower() >= self.segment.upper() {
             // if right exists, recurse
             if let Some(right) = self.right.as_ref() {
-                right.get_entry(key)
-            // otherwise, key doesn't exist
-            } else { None }
+                right.overlaps(segment)
+            // otherwise, nothing overlaps
+            } else { false }
+        // if self segment is empty, it cannot overlap, but either side might
+        } else if self.segment.is_empty() {
+            self.left.as_ref().as_ref().is_some_and(|left| left.overlaps(segment)) ||
+                self.right.as_ref().as_ref().is_some_and(|right| right.overlaps(segment))
+        // otherwise, segments overlap
+        } else { true }
+    }
+
+    // whether try_insert would accept segment, without modifying anything
+    pub fn can_insert(&self, segment: &Segment<K>) -> bool {
+        let mut current = Some(self);
+        while let Some(node) = current {
+            // if the segments perfectly overlap
+            if (segment.lower() == node.segment.lower()) && (segment.upper() == node.segment.upper()) {
+                return false;
+            // if segment is less than node segment, descend left
+            } else if segment.upper() <= node.segment.lower() {
+                current = (*node.left).as_ref();
+            // if segment is greater than node segment, descend right
+            } else if segment.lower() >= node.segment.upper() {
+                current = (*node.right).as_ref();
+            // otherwise, segments overlap in some (non-perfect) way
+            } else {
+                return false;
+            }
         }
+        true
     }
 
     pub fn insert(&mut self, segment: Segment<K>, value: V) {
+        if self.try_insert(segment, value).is_err() {
+            panic!("segments must not overlap");
+        }
+    }
+
+    pub fn try_insert(&mut self, segment: Segment<K>, value: V) -> Result<(), OverlapError<K, V>> {
+        self.try_insert_with(segment, value, |segment, value| SegmentMapNode::new(segment, value, None, None))
+    }
+
+    pub fn try_insert_with<F>(&mut self, segment: Segment<K>, value: V, new: F) -> Result<(), OverlapError<K, V>>
+    where
+        F: FnOnce(Segment<K>, V) -> SegmentMapNode<K, V>
+    {
+        let result = self.try_insert_unsized(segment, value, new);
+        self.resize();
+        result
+    }
+
+    fn try_insert_unsized<F>(&mut self, segment: Segment<K>, value: V, new: F) -> Result<(), OverlapError<K, V>>
+    where
+        F: FnOnce(Segment<K>, V) -> SegmentMapNode<K, V>
+    {
         // if the segments perfectly overlap (this prevents inserting duplicate empty segments)
         if (segment.lower() == self.segment.lower()) && (segment.upper() == self.segment.upper()) {
-            panic!("segments must not overlap");
+            Err(OverlapError::new(segment, value))
         // if segment is less than self segment
         } else if segment.upper() <= self.segment.lower() {
             // if left exists, recurse
             if let Some(left) = self.left.as_mut() {
-                left.insert(segment, value);
+                left.try_insert_with(segment, value, new)
             // otherwise, set new left
             } else {
-                self.left = Box::new(Some(SegmentMapNode::new(segment, value, None, None)));
+                *self.left = Some(new(segment, value));
+                Ok(())
             }
         // if segment is greater than self segment
         } else if segment.lower() >= self.segment.upper() {
             // if right exists, recurse
             if let Some(right) = self.right.as_mut() {
-                right.insert(segment, value);
+                right.try_insert_with(segment, value, new)
             // otherwise, set new right
             } else {
-                self.right = Box::new(Some(SegmentMapNode::new(segment, value, None, None)));
+                *self.right = Some(new(segment, value));
+                Ok(())
             }
         // otherwise, segments overlap in some (non-perfect) way
         } else {
-            panic!("segments must not overlap");
+            Err(OverlapError::new(segment, value))
         }
     }
 }
@@ -118,11 +289,15 @@ where
     K: Clone + PartialOrd,
     V: Clone,
 {
-    pub fn remove(mut self, segment: &Segment<K>) -> Option<SegmentMapNode<K, V>> {
+    pub fn remove(self, segment: &Segment<K>) -> Option<SegmentMapNode<K, V>> {
+        self.remove_unsized(segment).map(SegmentMapNode::resized)
+    }
+
+    fn remove_unsized(mut self, segment: &Segment<K>) -> Option<SegmentMapNode<K, V>> {
         // empty segments can be removed
         if segment.is_empty() {
             // if empty segment is enclosed by self segment, (potentially) split the segment
-            if self.segment.encloses(&segment) {
+            if self.segment.encloses(segment) {
                 // if empty segment exactly equals self segment
                 if (segment.lower() == self.segment.lower()) && (segment.upper() == self.segment.upper()) {
                     // remove self
@@ -210,96 +385,113 @@ where
                 } // otherwise, nothing to remove
                 Some(self)
             }
-        // if the segments overlap
-        } else if let Some(intersection) = segment.intersection(&self.segment) {
-            // if the overlap is empty, handle specially to prevent infinite recursion
-            if intersection.is_empty() {
-                // if segment is touching the right
-                if segment.lower() == self.segment.upper() {
-                    // if right exists, recurse
-                    if let Some(right) = self.right.take() {
-                        self.right = Box::new(right.remove(segment));
-                    } // otherwise, nothing to remove
-                // otherwise, segment is touching the left
-                } else {
-                    // if left exists, recurse
-                    if let Some(left) = self.left.take() {
-                        self.left = Box::new(left.remove(segment));
-                    } // otherwise, nothing to remove
-                }
-                Some(self)
-            // otherwise, the overlap must be removed
-            } else {
-                // remove self, will reinsert as needed
-                let mut result = match (*self.left, *self.right) {
-                    // two children, replace with right minimum
-                    (Some(left), Some(right)) => {
-                        let (right, mut result) = right.remove_min_node();
-                        result.right = Box::new(right);
-                        result.left = Box::new(Some(left));
-                        Some(result)
-                    },
-                    // one left child, move up
-                    (Some(left), None) => Some(left),
-                    // one right child, move up
-                    (None, Some(right)) => Some(right),
-                    // no children, simply remove
-                    (None, None) => None,
-                };
-                // if left part of segment still needs to be removed
-                if segment.lower() < intersection.lower() {
+        // otherwise, classify how the segments intersect
+        } else {
+            match segment.classify_intersection(&self.segment) {
+                // if self segment is empty and strictly within segment, remove self and the rest
+                IntersectKind::Touch(_) if self.segment.is_empty() && (segment.lower() < self.segment.lower()) && (self.segment.upper() < segment.upper()) => {
+                    // remove self, segment may extend to either side
+                    let result = match (*self.left, *self.right) {
+                        // two children, replace with right minimum
+                        (Some(left), Some(right)) => {
+                            let (right, mut result) = right.remove_min_node();
+                            result.right = Box::new(right);
+                            result.left = Box::new(Some(left));
+                            Some(result)
+                        },
+                        // one left child, move up
+                        (Some(left), None) => Some(left),
+                        // one right child, move up
+                        (None, Some(right)) => Some(right),
+                        // no children, simply remove
+                        (None, None) => None,
+                    };
                     // if result exists, do plain remove
-                    result = if let Some(result) = result {
-                        result.remove(&Segment::new(segment.lower().clone(), intersection.lower().clone()))
-                    // otherwise, nothing to remove
-                    } else { None };
-                // if left part of self still exists, reinsert
-                } else if self.segment.lower() < intersection.lower() {
-                    let segment = Segment::new(self.segment.lower().clone(), intersection.lower().clone());
-                    // if result exists, do plain insert
-                    if let Some(result) = result.as_mut() {
-                        result.insert(segment, self.value.clone());
-                    // otherwise, this is the new result
+                    result.and_then(|result| result.remove(segment))
+                },
+                // if the overlap is empty, handle specially to prevent infinite recursion
+                IntersectKind::Touch(_) => {
+                    // if segment is touching the right
+                    if segment.lower() == self.segment.upper() {
+                        // if right exists, recurse
+                        if let Some(right) = self.right.take() {
+                            self.right = Box::new(right.remove(segment));
+                        } // otherwise, nothing to remove
+                    // otherwise, segment is touching the left
                     } else {
-                        result = Some(SegmentMapNode::new(segment, self.value.clone(), None, None));
+                        // if left exists, recurse
+                        if let Some(left) = self.left.take() {
+                            self.left = Box::new(left.remove(segment));
+                        } // otherwise, nothing to remove
                     }
-                }
-                // if right part of segment still needs to be removed
-                if segment.upper() > intersection.upper() {
-                    // if result exists, do plain remove
-                    result = if let Some(result) = result {
-                        result.remove(&Segment::new(intersection.upper().clone(), segment.upper().clone()))
-                    // otherwise, nothing to remove
-                    } else { None };
-                // if right part of self still exists, reinsert
-                } else if self.segment.upper() > intersection.upper() {
-                    let segment = Segment::new(intersection.upper().clone(), self.segment.upper().clone());
-                    // if result exists, do plain insert
-                    if let Some(result) = result.as_mut() {
-                        result.insert(segment, self.value);
-                    // otherwise, this is the new result
+                    Some(self)
+                },
+                // otherwise, the overlap must be removed
+                IntersectKind::Overlap(intersection) => {
+                    // remove self, will reinsert as needed
+                    let mut result = match (*self.left, *self.right) {
+                        // two children, replace with right minimum
+                        (Some(left), Some(right)) => {
+                            let (right, mut result) = right.remove_min_node();
+                            result.right = Box::new(right);
+                            result.left = Box::new(Some(left));
+                            Some(result)
+                        },
+                        // one left child, move up
+                        (Some(left), None) => Some(left),
+                        // one right child, move up
+                        (None, Some(right)) => Some(right),
+                        // no children, simply remove
+                        (None, None) => None,
+                    };
+                    // if parts of segment beyond self still need to be removed, remove them all at once
+                    // (removing each side separately would miss empty segments at intersection bounds)
+                    if (segment.lower() < intersection.lower()) || (segment.upper() > intersection.upper()) {
+                        // if result exists, do plain remove
+                        result = result.and_then(|result| result.remove(segment));
+                    }
+                    // if left part of self still exists, reinsert
+                    if self.segment.lower() < intersection.lower() {
+                        let segment = Segment::new(self.segment.lower().clone(), intersection.lower().clone());
+                        // if result exists, do plain insert
+                        if let Some(result) = result.as_mut() {
+                            result.insert(segment, self.value.clone());
+                        // otherwise, this is the new result
+                        } else {
+                            result = Some(SegmentMapNode::new(segment, self.value.clone(), None, None));
+                        }
+                    }
+                    // if right part of self still exists, reinsert
+                    if self.segment.upper() > intersection.upper() {
+                        let segment = Segment::new(intersection.upper().clone(), self.segment.upper().clone());
+                        // if result exists, do plain insert
+                        if let Some(result) = result.as_mut() {
+                            result.insert(segment, self.value);
+                        // otherwise, this is the new result
+                        } else {
+                            result = Some(SegmentMapNode::new(segment, self.value, None, None));
+                        }
+                    }
+                    result
+                },
+                // otherwise, segments do not overlap
+                IntersectKind::Disjoint => {
+                    // if segment is greater than self segment
+                    if segment.lower() > self.segment.upper() {
+                        // if right exists, recurse
+                        if let Some(right) = self.right.take() {
+                            self.right = Box::new(right.remove(segment));
+                        } // otherwise, there is nothing to remove
+                    // otherwise segment is less than self segment
                     } else {
-                        result = Some(SegmentMapNode::new(segment, self.value, None, None));
+                        // if left exists, recurse
+                        if let Some(left) = self.left.take() {
+                            self.left = Box::new(left.remove(segment));
+                        } // otherwise, there is nothing to remove
                     }
-                }
-                result
-            }
-        // otherwise, segments do not overlap
-        } else {
-            // if segment is greater than self segment
-            if segment.lower() > self.segment.upper() {
-                // if right exists, recurse
-                if let Some(right) = self.right.take() {
-                    self.right = Box::new(right.remove(segment));
-                } // otherwise, there is nothing to remove
-            // otherwise segment is less than self segment
-            } else {
-                // if left exists, recurse
-                if let Some(left) = self.left.take() {
-                    self.left = Box::new(left.remove(segment));
-                } // otherwise, there is nothing to remove
+                    Some(self)
+                },
             }
-            Some(self)
         }
     }
 
@@ -310,14 +502,21 @@ where
         self.update_entry(segment, |_, v| value(v))
     }
 
-    pub fn update_entry<F>(mut self, segment: &Segment<K>, value: F) -> Option<SegmentMapNode<K, V>>
+    pub fn update_entry<F>(self, segment: &Segment<K>, value: F) -> Option<SegmentMapNode<K, V>>
+    where
+        F: Fn(&Segment<K>, Option<V>) -> Option<V> + Clone
+    {
+        self.update_entry_unsized(segment, value).map(SegmentMapNode::resized)
+    }
+
+    fn update_entry_unsized<F>(mut self, segment: &Segment<K>, value: F) -> Option<SegmentMapNode<K, V>>
     where
         F: Fn(&Segment<K>, Option<V>) -> Option<V> + Clone
     {
         // empty segments can be updated
         if segment.is_empty() {
             // if empty segment is enclosed by self segment, (potentially) split the segment
-            if self.segment.encloses(&segment) {
+            if self.segment.encloses(segment) {
                 // if empty segment exactly equals self segment
                 if (segment.lower() == self.segment.lower()) && (segment.upper() == self.segment.upper()) {
                     // remove self, will reinsert as needed
@@ -337,7 +536,7 @@ where
                         (None, None) => None,
                     };
                     // if update produces a value, reinsert
-                    if let Some(value) = value(&segment, Some(self.value.clone())) {
+                    if let Some(value) = value(segment, Some(self.value.clone())) {
                         // if result exists, do plain insert
                         if let Some(result) = result.as_mut() {
                             result.insert(segment.clone(), value);
@@ -386,7 +585,7 @@ where
                         (None, None) => None,
                     };
                     // if update produces a value, reinsert
-                    if let Some(value) = value(&segment, Some(self.value.clone())) {
+                    if let Some(value) = value(segment, Some(self.value.clone())) {
                         // if result exists, do plain insert
                         if let Some(result) = result.as_mut() {
                             result.insert(segment.clone(), value);
@@ -438,8 +637,31 @@ where
             }
         // if the segments overlap
         } else if let Some(intersection) = segment.intersection(&self.segment) {
+            // if self segment is empty and strictly within segment, remove self and update the rest
+            if self.segment.is_empty() && (segment.lower() < self.segment.lower()) && (self.segment.upper() < segment.upper()) {
+                // remove self, segment may extend to either side
+                let result = match (*self.left, *self.right) {
+                    // two children, replace with right minimum
+                    (Some(left), Some(right)) => {
+                        let (right, mut result) = right.remove_min_node();
+                        result.right = Box::new(right);
+                        result.left = Box::new(Some(left));
+                        Some(result)
+                    },
+                    // one left child, move up
+                    (Some(left), None) => Some(left),
+                    // one right child, move up
+                    (None, Some(right)) => Some(right),
+                    // no children, simply remove
+                    (None, None) => None,
+                };
+                // if result exists, do plain update
+                if let Some(result) = result {
+                    result.update_entry(segment, value)
+                // otherwise, if update produces a value, this is the new result
+                } else { value(segment, None).map(|value| SegmentMapNode::new(segment.clone(), value, None, None)) }
             // if the overlap is empty, handle specially to prevent infinite recursion
-            if intersection.is_empty() {
+            } else if intersection.is_empty() {
                 // if segment is touching the right
                 if segment.lower() == self.segment.upper() {
                     // if right exists, recurse
@@ -495,10 +717,10 @@ where
                     result = if let Some(result) = result {
                         result.update_entry(&segment, value.clone())
                     // otherwise, if update produces a value, this is the new result
-                    } else if let Some(value) = value(&segment, None) {
-                        Some(SegmentMapNode::new(segment, value, None, None))
-                    // otherwise, no result
-                    } else { None }
+                    } else { value(&segment, None).map(|value| SegmentMapNode::new(segment, value, None, None)) };
+                    // an empty segment at intersection lower is strictly within segment, so it is absorbed
+                    let bound = Segment::new(intersection.lower().clone(), intersection.lower().clone());
+                    result = result.and_then(|result| result.remove(&bound));
                 // if left part of self still exists, reinsert
                 } else if self.segment.lower() < intersection.lower() {
                     let segment = Segment::new(self.segment.lower().clone(), intersection.lower().clone());
@@ -516,11 +738,11 @@ where
                     // if result exists, do plain update
                     result = if let Some(result) = result {
                         result.update_entry(&segment, value)
-                    // otherwise, if update produces value, this is the new result
-                    } else if let Some(value) = value(&segment, None) {
-                        Some(SegmentMapNode::new(segment, value, None, None))
-                    // otherwise, no result
-                    } else { None }
+                    // otherwise, if update produces a value, this is the new result
+                    } else { value(&segment, None).map(|value| SegmentMapNode::new(segment, value, None, None)) };
+                    // an empty segment at intersection upper is strictly within segment, so it is absorbed
+                    let bound = Segment::new(intersection.upper().clone(), intersection.upper().clone());
+                    result = result.and_then(|result| result.remove(&bound));
                 // if right part of self still exists, reinsert
                 } else if self.segment.upper() > intersection.upper() {
                     let segment = Segment::new(intersection.upper().clone(), self.segment.upper().clone());
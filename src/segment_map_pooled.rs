@@ -0,0 +1,187 @@
+use crate::{
+    segment_map_node::SegmentMapNode,
+    OverlapError,
+    Segment,
+    SegmentMap,
+};
+use std::{
+    iter::FromIterator,
+    ops::{
+        Deref,
+        DerefMut,
+        Range,
+    },
+};
+
+/// A `SegmentMap` that keeps the allocations of freed nodes for reuse by later inserts.
+/// `clear`, `pop_min` and `pop_max` return allocations to the pool. The rest of the
+/// `SegmentMap` API is available through `DerefMut`, but methods such as `remove` and
+/// `update` free the nodes they discard rather than pooling them.
+pub struct SegmentMapPooled<K, V> {
+    map: SegmentMap<K, V>,
+    pool: Vec<Box<Option<SegmentMapNode<K, V>>>>,
+}
+
+impl<K, V> SegmentMapPooled<K, V>
+where
+    K: PartialOrd
+{
+    pub fn new() -> SegmentMapPooled<K, V> {
+        SegmentMapPooled {
+            map: SegmentMap::new(),
+            pool: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, segment: Segment<K>, value: V) {
+        if self.try_insert(segment, value).is_err() {
+            panic!("segments must not overlap");
+        }
+    }
+
+    pub fn try_insert(&mut self, segment: Segment<K>, value: V) -> Result<(), OverlapError<K, V>> {
+        let pool = &mut self.pool;
+        self.map.try_insert_with(segment, value, |segment, value| SegmentMapNode {
+            segment,
+            value,
+            left: pool.pop().unwrap_or_else(|| Box::new(None)),
+            right: pool.pop().unwrap_or_else(|| Box::new(None)),
+            size: 1,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        let pool = &mut self.pool;
+        self.map.clear_with(|node| pool.push(node));
+    }
+
+    pub fn pop_min(&mut self) -> Option<(Segment<K>, V)> {
+        let pool = &mut self.pool;
+        self.map.pop_min_with(|node| pool.push(node))
+    }
+
+    pub fn pop_max(&mut self) -> Option<(Segment<K>, V)> {
+        let pool = &mut self.pool;
+        self.map.pop_max_with(|node| pool.push(node))
+    }
+
+    /// Releases the allocations kept for reuse by `clear`.
+    pub fn shrink_to_fit(&mut self) {
+        self.pool = Vec::new();
+    }
+
+    pub fn into_inner(self) -> SegmentMap<K, V> {
+        self.map
+    }
+}
+
+impl<K, V> Default for SegmentMapPooled<K, V>
+where
+    K: PartialOrd
+{
+    fn default() -> SegmentMapPooled<K, V> {
+        SegmentMapPooled::new()
+    }
+}
+
+impl<K, V> Deref for SegmentMapPooled<K, V> {
+    type Target = SegmentMap<K, V>;
+
+    fn deref(&self) -> &SegmentMap<K, V> {
+        &self.map
+    }
+}
+
+impl<K, V> DerefMut for SegmentMapPooled<K, V> {
+    fn deref_mut(&mut self) -> &mut SegmentMap<K, V> {
+        &mut self.map
+    }
+}
+
+impl<K, V> Extend<(Segment<K>, V)> for SegmentMapPooled<K, V>
+where
+    K: PartialOrd
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (Segment<K>, V)>
+    {
+        for (segment, value) in iter {
+            self.insert(segment, value);
+        }
+    }
+}
+
+impl<K, V> Extend<(Range<K>, V)> for SegmentMapPooled<K, V>
+where
+    K: PartialOrd
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (Range<K>, V)>
+    {
+        for (range, value) in iter {
+            self.insert(Segment::new(range.start, range.end), value);
+        }
+    }
+}
+
+impl<K, V> FromIterator<(Segment<K>, V)> for SegmentMapPooled<K, V>
+where
+    K: PartialOrd
+{
+    fn from_iter<I>(iter: I) -> SegmentMapPooled<K, V>
+    where
+        I: IntoIterator<Item = (Segment<K>, V)>
+    {
+        let mut segment_map = SegmentMapPooled::new();
+        segment_map.extend(iter);
+        segment_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Segment,
+        SegmentMapPooled,
+    };
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut segment_map = SegmentMapPooled::new();
+        segment_map.insert(Segment::new(0, 4), 0);
+        segment_map.insert(Segment::new(4, 8), 1);
+        segment_map.clear();
+        assert_eq!(4, segment_map.pool.len());
+        segment_map.shrink_to_fit();
+        assert!(segment_map.is_empty());
+        assert_eq!(0, segment_map.pool.capacity());
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let mut segment_map = vec![
+            (Segment::new(0, 4), 0),
+            (Segment::new(4, 8), 1),
+            (Segment::new(8, 12), 2),
+        ].into_iter().collect::<SegmentMapPooled<_, _>>();
+        segment_map.extend(vec![(12..16, 3)]);
+        segment_map.remove(&Segment::new(2, 6));
+        segment_map.update(&Segment::new(10, 20), |value| Some(value.unwrap_or(4) * 10));
+        assert_eq!(vec![
+            (&Segment::new(0, 2), &0),
+            (&Segment::new(6, 8), &1),
+            (&Segment::new(8, 10), &2),
+            (&Segment::new(10, 12), &20),
+            (&Segment::new(12, 16), &30),
+            (&Segment::new(16, 20), &40),
+        ], segment_map.iter().collect::<Vec<_>>());
+
+        // popped nodes return their allocations to the pool
+        assert_eq!(Some((Segment::new(0, 2), 0)), segment_map.pop_min());
+        assert_eq!(Some((Segment::new(16, 20), 40)), segment_map.pop_max());
+        assert_eq!(4, segment_map.pool.len());
+        assert_eq!(4, segment_map.len());
+    }
+}
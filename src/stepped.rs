@@ -0,0 +1,66 @@
+use crate::{
+    Bounded,
+    Next,
+    Previous,
+};
+use std::iter::Step;
+
+/// Wraps any `std::iter::Step` type so it can be used as a discrete key. A wrapper is used,
+/// rather than a blanket impl, so enabling the `step` feature never removes the built-in
+/// `Next` and `Previous` impls.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Stepped<T>(pub T);
+
+impl<T> Next for Stepped<T>
+where
+    T: Step
+{
+    fn next_checked(&self) -> Option<Stepped<T>> { T::forward_checked(self.0.clone(), 1).map(Stepped) }
+}
+
+impl<T> Previous for Stepped<T>
+where
+    T: Step
+{
+    fn previous_checked(&self) -> Option<Stepped<T>> { T::backward_checked(self.0.clone(), 1).map(Stepped) }
+}
+
+impl<T> Bounded for Stepped<T>
+where
+    T: Bounded
+{
+    fn min() -> Stepped<T> { Stepped(T::min()) }
+    fn max() -> Stepped<T> { Stepped(T::max()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Next,
+        Previous,
+        Segment,
+        Stepped,
+    };
+    use std::iter::Step;
+
+    #[derive(Clone, Debug, PartialEq, PartialOrd)]
+    struct Id(u8);
+
+    impl Step for Id {
+        fn steps_between(start: &Id, end: &Id) -> (usize, Option<usize>) { u8::steps_between(&start.0, &end.0) }
+        fn forward_checked(start: Id, count: usize) -> Option<Id> { u8::forward_checked(start.0, count).map(Id) }
+        fn backward_checked(start: Id, count: usize) -> Option<Id> { u8::backward_checked(start.0, count).map(Id) }
+    }
+
+    #[test]
+    fn test_stepped() {
+        assert_eq!(Some(Stepped(Id(4))), Stepped(Id(3)).next_checked());
+        assert_eq!(None, Stepped(Id(255)).next_checked());
+        assert_eq!(Some(Stepped(Id(2))), Stepped(Id(3)).previous_checked());
+        assert_eq!(None, Stepped(Id(0)).previous_checked());
+        assert_eq!(Segment::new(Stepped(Id(3)), Stepped(Id(4))), Segment::singleton(Stepped(Id(3))));
+        // built-in impls remain available alongside the feature
+        assert_eq!(Some(1.0 + f64::EPSILON), 1.0f64.next_checked());
+        assert_eq!(Some(4u32), 5u32.previous_checked());
+    }
+}
@@ -0,0 +1,69 @@
+// Runs as its own test binary, since the counting allocator replaces the global allocator
+// for every test in the binary.
+
+use segment_map::{
+    Segment,
+    SegmentMapPooled,
+};
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System,
+    },
+    cell::Cell,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_reuse() {
+    let mut segment_map = SegmentMapPooled::new();
+    let mut allocations = Vec::new();
+    for _ in 0..3 {
+        let before = ALLOCATIONS.with(Cell::get);
+        for key in [5, 2, 8, 1, 3, 7, 9] {
+            segment_map.insert(Segment::new(key * 10, key * 10 + 10), key);
+        }
+        allocations.push(ALLOCATIONS.with(Cell::get) - before);
+        assert_eq!(Some(&3), segment_map.get(&35));
+        segment_map.clear();
+        assert!(segment_map.is_empty());
+    }
+    assert_eq!(14, allocations[0]);
+    assert_eq!(vec![0, 0], allocations[1..].to_vec());
+}
+
+#[test]
+fn test_reuse_popped() {
+    let mut segment_map = SegmentMapPooled::new();
+    for key in [5, 2, 8, 1, 3, 7, 9] {
+        segment_map.insert(Segment::new(key * 10, key * 10 + 10), key);
+    }
+    while segment_map.pop_min().is_some() {}
+    // popped nodes supply the allocations for new ones
+    let before = ALLOCATIONS.with(Cell::get);
+    for key in [4, 1, 6] {
+        segment_map.insert(Segment::new(key * 10, key * 10 + 10), key);
+    }
+    assert_eq!(0, ALLOCATIONS.with(Cell::get) - before);
+    assert_eq!(Some(&6), segment_map.get(&65));
+}